@@ -20,4 +20,19 @@ pub struct DescribeOptions {
     /// Number of spots to skip before describing
     #[clap(short = 's', long, default_value = "0")]
     pub skip: usize,
+
+    /// Number of threads to use
+    ///
+    /// [0: all available cores]
+    #[clap(short = 'T', long, default_value_t = 1)]
+    threads: u64,
+}
+impl DescribeOptions {
+    pub fn threads(&self) -> u64 {
+        if self.threads == 0 {
+            num_cpus::get() as u64
+        } else {
+            self.threads.min(num_cpus::get() as u64)
+        }
+    }
 }
@@ -1,7 +1,8 @@
-use crate::output::Compression;
+use crate::output::{ChunkLimits, Codec, Compression, OutputFileType};
+use anyhow::{bail, Result};
 use clap::Parser;
 
-use super::{FilterOptions, InputOptions, RuntimeOptions};
+use super::{parse_memory_size, FilterOptions, InputOptions, QualityOptions, RuntimeOptions};
 
 #[derive(Parser, Debug)]
 pub struct DumpArgs {
@@ -14,6 +15,9 @@ pub struct DumpArgs {
     #[clap(flatten)]
     pub output: DumpOutput,
 
+    #[clap(flatten)]
+    pub quality: QualityOptions,
+
     #[clap(flatten)]
     pub runtime: RuntimeOptions,
 }
@@ -34,16 +38,92 @@ pub struct DumpOutput {
     #[clap(short = 'n', long, requires = "split")]
     pub named_pipes: bool,
 
+    /// Stream the outputs to inherited file descriptors (e.g. `--fd 3,4`)
+    ///
+    /// One descriptor per output file in segment order, or a single one for interleaved output.
+    /// Without `-I`, split output is streamed for each segment of the archive.
+    /// Meant for bash process substitution: `3> >(cmd1) 4> >(cmd2)`
+    #[clap(long, value_delimiter = ',', conflicts_with_all = ["named_pipes", "unix_socket", "tcp"])]
+    pub fd: Vec<i32>,
+
+    /// Stream the outputs to listening Unix domain sockets
+    ///
+    /// One socket path per output file in segment order, or a single one for interleaved output
+    #[clap(long, value_delimiter = ',', conflicts_with_all = ["named_pipes", "tcp"])]
+    pub unix_socket: Vec<String>,
+
+    /// Stream the outputs to listening TCP endpoints (<host>:<port>)
+    ///
+    /// One endpoint per output file in segment order, or a single one for interleaved output
+    #[clap(long, value_delimiter = ',', conflicts_with = "named_pipes")]
+    pub tcp: Vec<String>,
+
     /// Output Format
+    ///
+    /// SAM and BAM records are unaligned and written interleaved to stdout.
+    /// BAM output is always BGZF compressed.
     #[clap(short = 'f', long, default_value = "q")]
     pub format: OutputFormat,
 
+    /// Row granularity of columnar output (Parquet/Arrow IPC)
+    #[clap(long, default_value = "segment")]
+    pub rows: ColumnarRows,
+
+    /// Store technical segments as tags of the biological reads (SAM/BAM only)
+    ///
+    /// Technical segments are not written as separate records in this mode
+    #[clap(long)]
+    pub tech_tag: Option<TechTag>,
+
+    /// Read identifier (defline) style
+    ///
+    /// The `orig` style is rejected: original read names are not available from the
+    /// archive reader.
+    #[clap(short = 'D', long, default_value = "xsra")]
+    pub defline: DeflineStyle,
+
+    /// Custom sequence defline template (overrides the style)
+    ///
+    /// Placeholders: $ac (accession), $si (spot ID), $sd (segment ID),
+    /// $ri (read number: segment ID + 1), $rl (read length).
+    ///
+    /// $sn (original read name) and $sg (spot group) are rejected: they are not available
+    /// from the archive reader.
+    #[clap(long)]
+    pub defline_seq: Option<String>,
+
+    /// Custom quality defline template (overrides the style)
+    ///
+    /// Uses the same placeholders as `--defline-seq` (FASTQ only)
+    #[clap(long)]
+    pub defline_qual: Option<String>,
+
     /// Split read segments to separate files
     ///
     /// Default will output interleaved reads to stdout
     #[clap(short = 's', long)]
     pub split: bool,
 
+    /// Split biological mates as fasterq-dump --split-3
+    ///
+    /// Spots with both mates passing the filters are written to <prefix>1 and <prefix>2,
+    /// lone surviving mates (and single-end reads) are written to <prefix>unpaired.
+    /// Technical segments are never written in this mode.
+    #[clap(long = "split-3", conflicts_with = "split")]
+    pub split3: bool,
+
+    /// Concatenate the selected segments of each spot into a single record
+    ///
+    /// Records are written to stdout (as fastq-dump does without --split-files)
+    #[clap(short = 'C', long, conflicts_with_all = ["split", "split3"])]
+    pub concatenate: bool,
+
+    /// Base inserted between concatenated segments (e.g. 'N')
+    ///
+    /// Separator bases are given the lowest quality score ('!')
+    #[clap(long, requires = "concatenate")]
+    pub concat_separator: Option<char>,
+
     /// Prefix for segment files
     ///
     /// Output will follow the pattern: <outdir>/<prefix><segment>.<ext>
@@ -52,29 +132,317 @@ pub struct DumpOutput {
 
     /// Compress output files
     ///
-    /// [uncompressed, gzip, bgzip, zstd]
+    /// [uncompressed, gzip, bgzip, zstd, xz, bzip2 (j), lz4]
+    ///
+    /// Parquet applies the codec to its column chunks (gzip, zstd or lz4) and Arrow IPC
+    /// only supports zstd and lz4
     #[clap(short = 'c', long, default_value = "u")]
     pub compression: Compression,
 
+    /// Compression level of the codec
+    ///
+    /// [gzip/bgzip/xz: 0-9, zstd: 1-22, bzip2: 1-9, lz4: 0-12]
+    ///
+    /// Defaults to the codec's default level (zstd: 3). Ignored by Arrow IPC.
+    #[clap(long)]
+    pub compression_level: Option<i32>,
+
+    /// Index the bgzip compressed output files for random access
+    ///
+    /// Writes a BGZF block index (<file>.gzi) next to each output file, and for FASTA
+    /// a sequence index (<file>.fai), so the records can be fetched with `samtools faidx`.
+    /// Requires -cb. Not written for stdout or named pipes.
+    #[clap(long, conflicts_with_all = ["checkpoint", "resume"])]
+    pub index: bool,
+
     /// Keep empty files
     ///
     /// By default empty files will be deleted
     #[clap(short = 'E', long)]
     pub keep_empty: bool,
+
+    /// Start a new part of each output file after this many reads
+    ///
+    /// Parts follow the pattern: <outdir>/<prefix><segment>.part<NNNN>.<ext>
+    /// and only end at spot boundaries, so mate files always hold the same spots.
+    /// A part ends once any of its files holds this many reads (the last part may hold fewer).
+    /// Requires --split or --split-3.
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub chunk_reads: Option<u64>,
+
+    /// Start a new part of each output file before its records exceed this uncompressed size
+    ///
+    /// Measures the records before compression, so compressed parts are smaller on disk.
+    /// Parts end at spot boundaries, so a part only exceeds the size if a single spot does.
+    /// Accepts K/M/G suffixes (e.g. 2G).
+    /// Requires --split or --split-3.
+    #[clap(long, value_parser = parse_memory_size)]
+    pub chunk_uncompressed_size: Option<usize>,
+
+    /// Record the written spots in <outdir>/<prefix>checkpoint.json
+    ///
+    /// Requires --split or --split-3.
+    #[clap(long)]
+    pub checkpoint: bool,
+
+    /// Resume an interrupted dump from its checkpoint (implies --checkpoint)
+    ///
+    /// Output files are truncated to the last checkpoint and only the missing spots are
+    /// processed. New records are appended as additional gzip/zstd members.
+    /// The output, filter and sampling options must match those of the interrupted run.
+    #[clap(long)]
+    pub resume: bool,
+
+    /// Write a JSON manifest of all output files to this path once the dump finished
+    ///
+    /// Lists the format, compression, read/base counts, size and MD5/SHA-256 checksums
+    /// of each file along with the accession, its source and the xsra version.
+    #[clap(long, conflicts_with_all = ["checkpoint", "resume"])]
+    pub manifest: Option<String>,
+
+    /// Format of the final statistics
+    #[clap(long, default_value = "text")]
+    pub stats_format: StatsFormat,
+
+    /// Write the final statistics to this path instead of stderr
+    #[clap(long)]
+    pub stats_file: Option<String>,
+
+    /// Preserve the spot order of the archive
+    ///
+    /// Spots are still decoded on multiple threads but are written in archive order,
+    /// so the output is identical regardless of the number of threads.
+    #[clap(long)]
+    pub ordered: bool,
 }
+impl Default for DumpOutput {
+    /// Same as the command line defaults
+    fn default() -> Self {
+        Self {
+            outdir: "output".to_string(),
+            named_pipes: false,
+            fd: Vec::new(),
+            unix_socket: Vec::new(),
+            tcp: Vec::new(),
+            format: OutputFormat::Fastq,
+            rows: ColumnarRows::Segment,
+            tech_tag: None,
+            defline: DeflineStyle::Xsra,
+            defline_seq: None,
+            defline_qual: None,
+            split: false,
+            split3: false,
+            concatenate: false,
+            concat_separator: None,
+            prefix: "seg_".to_string(),
+            compression: Compression::Uncompressed,
+            compression_level: None,
+            index: false,
+            keep_empty: false,
+            chunk_reads: None,
+            chunk_uncompressed_size: None,
+            checkpoint: false,
+            resume: false,
+            manifest: None,
+            stats_format: StatsFormat::Text,
+            stats_file: None,
+            ordered: false,
+        }
+    }
+}
+
+impl DumpOutput {
+    /// Whether the written spots are recorded in a checkpoint
+    pub fn is_checkpointed(&self) -> bool {
+        self.checkpoint || self.resume
+    }
+
+    /// Streams replacing the output files (or stdout) in output order
+    ///
+    /// Empty unless one of `--fd`, `--unix-socket` or `--tcp` was given.
+    pub fn sinks(&self) -> Vec<OutputFileType<'_>> {
+        self.fd
+            .iter()
+            .map(|&fd| OutputFileType::FileDescriptor(fd))
+            .chain(
+                self.unix_socket
+                    .iter()
+                    .map(|path| OutputFileType::UnixSocket(path)),
+            )
+            .chain(self.tcp.iter().map(|addr| OutputFileType::Tcp(addr)))
+            .collect()
+    }
+
+    /// Whether the outputs are streamed to sockets or file descriptors
+    pub fn is_streamed(&self) -> bool {
+        !self.fd.is_empty() || !self.unix_socket.is_empty() || !self.tcp.is_empty()
+    }
+
+    /// Path of the checkpoint file
+    pub fn checkpoint_path(&self) -> String {
+        format!("{}/{}checkpoint.json", self.outdir, self.prefix)
+    }
 
-#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+    /// Compression codec of the outputs (BAM is always BGZF compressed)
+    pub fn codec(&self) -> Result<Codec> {
+        let compression = if self.format == OutputFormat::Bam {
+            Compression::Bgzip
+        } else {
+            self.compression
+        };
+        if self.index && self.format.is_columnar() {
+            bail!("Columnar outputs cannot be indexed");
+        }
+        Codec::new(compression, self.compression_level)?.with_index(self.index)
+    }
+
+    /// Thresholds of chunked output (`None` if the outputs are not chunked)
+    pub fn chunk_limits(&self) -> Option<ChunkLimits> {
+        ChunkLimits::new(
+            self.chunk_reads,
+            self.chunk_uncompressed_size.map(|size| size as u64),
+        )
+    }
+
+    pub fn layout(&self) -> OutputLayout {
+        if self.split {
+            OutputLayout::Split
+        } else if self.split3 {
+            OutputLayout::Split3
+        } else if self.concatenate {
+            OutputLayout::Concatenated
+        } else {
+            OutputLayout::Interleaved
+        }
+    }
+}
+
+/// Arrangement of the written reads across the output handles
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputLayout {
+    /// All reads interleaved to stdout
+    Interleaved,
+    /// One file per segment
+    Split,
+    /// Paired mates in two files and lone mates in a third
+    Split3,
+    /// All segments of a spot joined into a single read to stdout
+    Concatenated,
+}
+impl OutputLayout {
+    /// Whether reads are split across multiple output files
+    pub fn is_split(&self) -> bool {
+        matches!(self, Self::Split | Self::Split3)
+    }
+
+    /// Names of the output files in handle order (`None` for handles without a file)
+    pub fn output_names(&self, include: &[usize]) -> Vec<Option<String>> {
+        match self {
+            Self::Interleaved | Self::Concatenated => Vec::new(),
+            Self::Split => (0..4)
+                .map(|i| (include.is_empty() || include.contains(&i)).then(|| i.to_string()))
+                .collect(),
+            Self::Split3 => ["1", "2", "unpaired"]
+                .iter()
+                .map(|name| Some(name.to_string()))
+                .collect(),
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
 pub enum OutputFormat {
     #[clap(name = "q", help = "FASTQ")]
     Fastq,
     #[clap(name = "a", help = "FASTA")]
     Fasta,
+    #[clap(name = "s", help = "Unaligned SAM")]
+    Sam,
+    #[clap(name = "b", help = "Unaligned BAM (BGZF compressed)")]
+    Bam,
+    #[clap(name = "p", help = "Parquet (written to <outdir>/<accession>.parquet)")]
+    Parquet,
+    #[clap(name = "i", help = "Arrow IPC stream")]
+    ArrowIpc,
 }
 impl OutputFormat {
     pub fn ext(&self) -> &str {
         match self {
             Self::Fasta => "fa",
             Self::Fastq => "fq",
+            Self::Sam => "sam",
+            Self::Bam => "bam",
+            Self::Parquet => "parquet",
+            Self::ArrowIpc => "arrow",
+        }
+    }
+
+    /// Whether the records are written as SAM/BAM alignments
+    pub fn is_sam(&self) -> bool {
+        matches!(self, Self::Sam | Self::Bam)
+    }
+
+    /// Whether the records are written as columnar record batches
+    pub fn is_columnar(&self) -> bool {
+        matches!(self, Self::Parquet | Self::ArrowIpc)
+    }
+}
+
+/// Format of the final dump statistics
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsFormat {
+    #[clap(name = "text", help = "Human readable summary")]
+    Text,
+    #[clap(name = "json", help = "JSON object")]
+    Json,
+    #[clap(name = "tsv", help = "Tab-separated metric, segment and value rows")]
+    Tsv,
+}
+
+/// Row granularity of columnar output
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColumnarRows {
+    #[clap(name = "segment", help = "One row per segment")]
+    Segment,
+    #[clap(name = "spot", help = "One row per spot (segment columns are lists)")]
+    Spot,
+}
+
+/// SAM tag pair used to store technical segments
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TechTag {
+    #[clap(name = "bc", help = "Sample barcode (BC/QT)")]
+    Bc,
+    #[clap(name = "rx", help = "Molecular barcode / UMI (RX/QX)")]
+    Rx,
+}
+impl TechTag {
+    /// The sequence and quality tag names
+    pub fn tags(&self) -> (&'static str, &'static str) {
+        match self {
+            Self::Bc => ("BC", "QT"),
+            Self::Rx => ("RX", "QX"),
         }
     }
 }
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeflineStyle {
+    #[clap(name = "xsra", help = "@<spot>.<segment>")]
+    Xsra,
+    #[clap(
+        name = "fastq-dump",
+        help = "@<accession>.<spot> <spot> length=<len> (repeated on the '+' line)"
+    )]
+    FastqDump,
+    #[clap(
+        name = "fasterq-dump",
+        help = "@<accession>.<spot> <spot> length=<len> (bare '+' line)"
+    )]
+    FasterqDump,
+    #[clap(
+        name = "orig",
+        help = "@<original read name> (as fastq-dump --origfmt, not available from this archive reader)"
+    )]
+    Original,
+}
@@ -1,5 +1,7 @@
 use clap::Parser;
 
+use super::TrimOptions;
+
 #[derive(Debug, Parser)]
 #[clap(next_help_heading = "FILTER OPTIONS")]
 pub struct FilterOptions {
@@ -15,6 +17,33 @@ pub struct FilterOptions {
     #[clap(short = 't', long)]
     pub skip_technical: bool,
 
+    /// Maximum fraction of N bases in a segment
+    #[clap(long)]
+    pub max_n_frac: Option<f64>,
+
+    /// Minimum mean Phred quality score of a segment
+    #[clap(long)]
+    pub min_mean_qual: Option<f64>,
+
+    /// Maximum expected errors of a segment
+    ///
+    /// The expected errors are the sum of the per-base error probabilities (10^(-Q/10))
+    #[clap(long)]
+    pub max_ee: Option<f64>,
+
+    /// Maximum DUST low-complexity score of a segment
+    ///
+    /// The triplet-based DUST score over the whole segment (homopolymers and short
+    /// tandem repeats score high).
+    #[clap(long)]
+    pub max_dust: Option<f64>,
+
+    /// Spot-level filter policy
+    ///
+    /// Determines how failing segments affect the other segments of their spot.
+    #[clap(long, default_value = "segment")]
+    pub spot_filter: SpotFilter,
+
     /// Only process up to N spots
     ///
     /// Note: this is not the number of reads, but the number of spots.
@@ -22,9 +51,82 @@ pub struct FilterOptions {
     #[clap(short = 'l', long)]
     pub limit: Option<u64>,
 
+    /// First spot ID to process (1-indexed, inclusive)
+    #[clap(short = 'N', long)]
+    pub start: Option<u64>,
+
+    /// Last spot ID to process (1-indexed, inclusive)
+    #[clap(short = 'X', long)]
+    pub stop: Option<u64>,
+
+    /// Randomly sample a fraction of spots (0, 1]
+    ///
+    /// The sample only depends on the seed and not on the number of threads
+    #[clap(long, conflicts_with = "sample_count")]
+    pub sample_frac: Option<f64>,
+
+    /// Randomly sample an exact number of spots
+    ///
+    /// The sample only depends on the seed and not on the number of threads
+    #[clap(long)]
+    pub sample_count: Option<u64>,
+
+    /// Seed of the random spot sampling
+    #[clap(long, default_value = "42")]
+    pub seed: u64,
+
+    /// Only process the spots listed in a file
+    ///
+    /// One spot ID (e.g. "42") or inclusive range (e.g. "100-200") per line.
+    /// Combined with the spot range options, only listed spots within the range are processed.
+    #[clap(long)]
+    pub spot_list: Option<String>,
+
     /// Only process specific segments
     ///
     /// Default: include all segments
     #[clap(short = 'I', long, num_args = 0.., value_delimiter = ',', required_if_eq("named_pipes", "true"))]
     pub include: Vec<usize>,
+
+    #[clap(flatten)]
+    pub trim: TrimOptions,
+}
+impl Default for FilterOptions {
+    /// Same as the command line defaults
+    fn default() -> Self {
+        Self {
+            min_read_len: 1,
+            skip_technical: false,
+            max_n_frac: None,
+            min_mean_qual: None,
+            max_ee: None,
+            max_dust: None,
+            spot_filter: SpotFilter::Segment,
+            limit: None,
+            start: None,
+            stop: None,
+            sample_frac: None,
+            sample_count: None,
+            seed: 42,
+            spot_list: None,
+            include: Vec::new(),
+            trim: TrimOptions::default(),
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpotFilter {
+    #[clap(name = "segment", help = "Drop failing segments on their own")]
+    Segment,
+    #[clap(
+        name = "any",
+        help = "Drop the whole spot if any included segment fails"
+    )]
+    Any,
+    #[clap(
+        name = "all",
+        help = "Drop the whole spot only if all included segments fail (otherwise keep all)"
+    )]
+    All,
 }
@@ -11,15 +11,22 @@ mod dump;
 mod filter;
 mod input;
 mod prefetch;
+mod quality;
 mod recode;
 mod runtime;
+mod trim;
 pub use describe::{DescribeArgs, DescribeOptions};
-pub use dump::{DumpArgs, DumpOutput, OutputFormat};
-pub use filter::FilterOptions;
+pub use dump::{
+    ColumnarRows, DeflineStyle, DumpArgs, DumpOutput, OutputFormat, OutputLayout, StatsFormat,
+    TechTag,
+};
+pub use filter::{FilterOptions, SpotFilter};
 pub use input::{AccessionOptions, InputOptions, MultiInputOptions, Provider};
 pub use prefetch::PrefetchArgs;
+pub use quality::QualityOptions;
 pub use recode::{BinseqFlavor, RecodeArgs};
 pub use runtime::RuntimeOptions;
+pub use trim::TrimOptions;
 
 const STYLES: Styles = Styles::styled()
     .header(AnsiColor::Green.on_default().effects(Effects::BOLD))
@@ -38,11 +45,11 @@ pub struct Cli {
 pub enum Command {
     /// Extract the records of the SRA file and output them as FASTQ
     #[clap(name = "dump")]
-    Dump(DumpArgs),
+    Dump(Box<DumpArgs>),
 
     /// Recode the records of the SRA file as BINSEQ
     #[clap(name = "recode")]
-    Recode(RecodeArgs),
+    Recode(Box<RecodeArgs>),
 
     /// Describe the read segments in the SRA file within a specified limit
     #[clap(name = "describe")]
@@ -52,3 +59,77 @@ pub enum Command {
     #[clap(name = "prefetch")]
     Prefetch(PrefetchArgs),
 }
+
+/// Parses a size with an optional K/M/G (binary) suffix
+pub(crate) fn parse_memory_size(input: &str) -> Result<usize, String> {
+    let input = input.trim().to_uppercase();
+    let last_char = input.chars().last().unwrap_or('0');
+
+    let (number_str, multiplier) = match last_char {
+        'K' => (&input[..input.len() - 1], 1024),
+        'M' => (&input[..input.len() - 1], 1024 * 1024),
+        'G' => (&input[..input.len() - 1], 1024 * 1024 * 1024),
+        _ if last_char.is_ascii_digit() => (input.as_str(), 1),
+        _ => return Err(format!("Invalid memory size format: {input}")),
+    };
+
+    match number_str.parse::<usize>() {
+        Ok(number) => Ok(number * multiplier),
+        Err(_) => Err(format!("Failed to parse number: {number_str}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // parse_memory_size tests
+    #[test]
+    fn parse_memory_size_k_suffix() {
+        assert_eq!(parse_memory_size("1K"), Ok(1024));
+    }
+
+    #[test]
+    fn parse_memory_size_m_suffix() {
+        assert_eq!(parse_memory_size("1M"), Ok(1024 * 1024));
+    }
+
+    #[test]
+    fn parse_memory_size_g_suffix() {
+        assert_eq!(parse_memory_size("1G"), Ok(1024 * 1024 * 1024));
+    }
+
+    #[test]
+    fn parse_memory_size_ascii_digit() {
+        assert_eq!(parse_memory_size("1024"), Ok(1024));
+    }
+
+    #[test]
+    fn parse_memory_size_invalid_format() {
+        assert!(parse_memory_size("512X").is_err());
+    }
+
+    #[test]
+    fn parse_memory_size_invalid_number() {
+        assert!(parse_memory_size("abcK").is_err());
+    }
+
+    // Default tests
+    #[test]
+    fn filter_options_default_matches_cli() {
+        let args = DumpArgs::parse_from(["xsra", "SRR000001"]);
+        assert_eq!(
+            format!("{:?}", FilterOptions::default()),
+            format!("{:?}", args.filter)
+        );
+    }
+
+    #[test]
+    fn dump_output_default_matches_cli() {
+        let args = DumpArgs::parse_from(["xsra", "SRR000001"]);
+        assert_eq!(
+            format!("{:?}", DumpOutput::default()),
+            format!("{:?}", args.output)
+        );
+    }
+}
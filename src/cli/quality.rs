@@ -0,0 +1,31 @@
+use clap::Parser;
+
+#[derive(Parser, Debug, Default)]
+#[clap(next_help_heading = "QUALITY OPTIONS")]
+pub struct QualityOptions {
+    /// Bin quality scores into the 8 Illumina levels
+    ///
+    /// [2-9: 6, 10-19: 15, 20-24: 22, 25-29: 27, 30-34: 33, 35-39: 37, 40+: 40]
+    #[clap(long)]
+    pub qual_bin: bool,
+
+    /// Replace all quality scores with a fixed value
+    #[clap(long, conflicts_with = "qual_bin")]
+    pub qual_fixed: Option<u8>,
+
+    /// Cap quality scores at a maximum value
+    #[clap(long)]
+    pub qual_cap: Option<u8>,
+
+    /// Write quality scores with a Phred+64 offset (Illumina 1.3-1.7)
+    ///
+    /// Scores above 62 cannot be represented and are capped.
+    #[clap(long)]
+    pub phred64: bool,
+}
+impl QualityOptions {
+    /// Whether any quality transform was requested
+    pub fn is_active(&self) -> bool {
+        self.qual_bin || self.qual_fixed.is_some() || self.qual_cap.is_some() || self.phred64
+    }
+}
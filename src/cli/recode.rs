@@ -1,4 +1,4 @@
-use super::{InputOptions, RuntimeOptions};
+use super::{parse_memory_size, InputOptions, QualityOptions, RuntimeOptions};
 use anyhow::{bail, Result};
 use clap::Parser;
 
@@ -15,9 +15,15 @@ pub struct RecodeArgs {
 
     #[clap(flatten)]
     pub output: RecodeOutput,
+
+    #[clap(flatten)]
+    pub quality: QualityOptions,
 }
 impl RecodeArgs {
     pub fn validate(&self) -> Result<()> {
+        if matches!(self.output.flavor, BinseqFlavor::Binseq) && self.quality.is_active() {
+            bail!("BINSEQ does not store quality scores (quality options require VBINSEQ)");
+        }
         match &self.selection.include.len() {
             0 => bail!(
                 "Recoding requires including at least one spot segment (see 'xsra recode --help' for usage)"
@@ -53,6 +59,37 @@ pub struct SelectionOptions {
     #[clap(short = 'l', long)]
     pub limit: Option<usize>,
 
+    /// First spot ID to process (1-indexed, inclusive)
+    #[clap(short = 'N', long)]
+    pub start: Option<u64>,
+
+    /// Last spot ID to process (1-indexed, inclusive)
+    #[clap(short = 'X', long)]
+    pub stop: Option<u64>,
+
+    /// Randomly sample a fraction of spots (0, 1]
+    ///
+    /// The sample only depends on the seed and not on the number of threads
+    #[clap(long, conflicts_with = "sample_count")]
+    pub sample_frac: Option<f64>,
+
+    /// Randomly sample an exact number of spots
+    ///
+    /// The sample only depends on the seed and not on the number of threads
+    #[clap(long)]
+    pub sample_count: Option<u64>,
+
+    /// Seed of the random spot sampling
+    #[clap(long, default_value = "42")]
+    pub seed: u64,
+
+    /// Only process the spots listed in a file
+    ///
+    /// One spot ID (e.g. "42") or inclusive range (e.g. "100-200") per line.
+    /// Combined with the spot range options, only listed spots within the range are processed.
+    #[clap(long)]
+    pub spot_list: Option<String>,
+
     /// Include specific segments (zero-indexed) as CSV
     ///
     /// I.e. to include the first and third segments, use "-I 0,2".
@@ -78,6 +115,13 @@ pub struct RecodeOutput {
     /// Only used by vbq
     #[clap(short = 'B', long, value_parser = parse_memory_size, default_value = "128K")]
     pub block_size: usize,
+
+    /// Write a JSON manifest of the output file to this path once recoding finished
+    ///
+    /// Lists the read/base counts, size and MD5/SHA-256 checksums of the file along with
+    /// the accession, its source and the xsra version.
+    #[clap(long)]
+    pub manifest: Option<String>,
 }
 impl RecodeOutput {
     pub fn name(&self) -> String {
@@ -105,57 +149,3 @@ impl BinseqFlavor {
         }
     }
 }
-
-fn parse_memory_size(input: &str) -> Result<usize, String> {
-    let input = input.trim().to_uppercase();
-    let last_char = input.chars().last().unwrap_or('0');
-
-    let (number_str, multiplier) = match last_char {
-        'K' => (&input[..input.len() - 1], 1024),
-        'M' => (&input[..input.len() - 1], 1024 * 1024),
-        'G' => (&input[..input.len() - 1], 1024 * 1024 * 1024),
-        _ if last_char.is_ascii_digit() => (input.as_str(), 1),
-        _ => return Err(format!("Invalid memory size format: {input}")),
-    };
-
-    match number_str.parse::<usize>() {
-        Ok(number) => Ok(number * multiplier),
-        Err(_) => Err(format!("Failed to parse number: {number_str}")),
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    // parse_memory_size tests
-    #[test]
-    fn parse_memory_size_k_suffix() {
-        assert_eq!(parse_memory_size("1K"), Ok(1024));
-    }
-
-    #[test]
-    fn parse_memory_size_m_suffix() {
-        assert_eq!(parse_memory_size("1M"), Ok(1024 * 1024));
-    }
-
-    #[test]
-    fn parse_memory_size_g_suffix() {
-        assert_eq!(parse_memory_size("1G"), Ok(1024 * 1024 * 1024));
-    }
-
-    #[test]
-    fn parse_memory_size_ascii_digit() {
-        assert_eq!(parse_memory_size("1024"), Ok(1024));
-    }
-
-    #[test]
-    fn parse_memory_size_invalid_format() {
-        assert!(parse_memory_size("512X").is_err());
-    }
-
-    #[test]
-    fn parse_memory_size_invalid_number() {
-        assert!(parse_memory_size("abcK").is_err());
-    }
-}
@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use clap::Parser;
 
 #[derive(Parser, Debug)]
@@ -8,6 +10,12 @@ pub struct RuntimeOptions {
     /// [0: all available cores]
     #[clap(short = 'T', long, default_value_t = 1)]
     threads: u64,
+
+    /// Print a progress line to stderr every N seconds
+    ///
+    /// Meant for batch logs: the live progress bar is only shown when stderr is a terminal.
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    progress_interval: Option<u64>,
 }
 impl RuntimeOptions {
     pub fn threads(&self) -> u64 {
@@ -17,4 +25,8 @@ impl RuntimeOptions {
             self.threads.min(num_cpus::get() as u64)
         }
     }
+
+    pub fn progress_interval(&self) -> Option<Duration> {
+        self.progress_interval.map(Duration::from_secs)
+    }
 }
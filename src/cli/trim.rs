@@ -0,0 +1,84 @@
+use clap::Parser;
+
+#[derive(Debug, Parser, Default)]
+#[clap(next_help_heading = "TRIM OPTIONS")]
+pub struct TrimOptions {
+    /// Trim the 3' end from the first window whose mean quality is below a threshold
+    ///
+    /// Given as <SIZE>:<QUALITY> (e.g. "4:20")
+    #[clap(long, value_parser = parse_window)]
+    pub trim_window: Option<SlidingWindow>,
+
+    /// Trim leading bases below a quality score
+    #[clap(long)]
+    pub trim_leading: Option<u8>,
+
+    /// Trim trailing bases below a quality score
+    #[clap(long)]
+    pub trim_trailing: Option<u8>,
+
+    /// Trim trailing N bases
+    #[clap(long)]
+    pub trim_trailing_n: bool,
+}
+/// A sliding quality window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlidingWindow {
+    /// Number of bases in the window
+    pub size: usize,
+    /// Minimum mean quality score of the window
+    pub quality: u8,
+}
+
+fn parse_window(input: &str) -> Result<SlidingWindow, String> {
+    let Some((size, quality)) = input.split_once(':') else {
+        return Err(format!(
+            "Invalid window format (expected <SIZE>:<QUALITY>): {input}"
+        ));
+    };
+    let size = size
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| format!("Failed to parse window size: {size}"))?;
+    let quality = quality
+        .trim()
+        .parse::<u8>()
+        .map_err(|_| format!("Failed to parse window quality: {quality}"))?;
+    if size == 0 {
+        return Err("Window size must be greater than zero".to_string());
+    }
+    Ok(SlidingWindow { size, quality })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // parse_window tests
+    #[test]
+    fn parse_window_valid() {
+        assert_eq!(
+            parse_window("4:20"),
+            Ok(SlidingWindow {
+                size: 4,
+                quality: 20
+            })
+        );
+    }
+
+    #[test]
+    fn parse_window_missing_separator() {
+        assert!(parse_window("420").is_err());
+    }
+
+    #[test]
+    fn parse_window_invalid_number() {
+        assert!(parse_window("a:20").is_err());
+        assert!(parse_window("4:300").is_err());
+    }
+
+    #[test]
+    fn parse_window_zero_size() {
+        assert!(parse_window("0:20").is_err());
+    }
+}
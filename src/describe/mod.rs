@@ -1,20 +1,20 @@
 use std::path::Path;
+use std::sync::Arc;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use ncbi_vdb_sys::{SegmentType, SraReader};
 
 use crate::{
     cli::{DescribeOptions, InputOptions},
     prefetch::identify_url,
+    quality::PHRED33_OFFSET,
+    spots::{SpotQueue, SpotRange, SPOT_CHUNK_SIZE},
 };
 
 mod stats;
 use stats::DescribeStats;
 
 fn calculate_average_quality(qual: &[u8]) -> f64 {
-    // PHRED33 has an ASCII offset of 33
-    const PHRED33_OFFSET: u8 = 33;
-
     if qual.is_empty() {
         return 0.0;
     }
@@ -24,41 +24,119 @@ fn calculate_average_quality(qual: &[u8]) -> f64 {
     total_score as f64 / qual.len() as f64
 }
 
-pub fn describe_inner(accession: &str, skip: usize, limit: usize) -> Result<DescribeStats> {
+/// Observed types, lengths and mean qualities of the segments of a set of spots
+#[derive(Default)]
+struct SegmentObservations {
+    /// Type of each segment (`None` until a segment was observed)
+    types: Vec<Option<SegmentType>>,
+    lengths: Vec<Vec<f64>>,
+    qualities: Vec<Vec<f64>>,
+}
+impl SegmentObservations {
+    fn resize(&mut self, num_segments: usize) {
+        if self.types.len() < num_segments {
+            self.types.resize(num_segments, None);
+            self.lengths.resize(num_segments, Vec::new());
+            self.qualities.resize(num_segments, Vec::new());
+        }
+    }
+
+    fn observe(&mut self, sid: usize, ty: SegmentType, len: usize, qual: &[u8]) {
+        self.resize(sid + 1);
+        self.types[sid] = Some(ty);
+        self.lengths[sid].push(len as f64);
+        self.qualities[sid].push(calculate_average_quality(qual));
+    }
+
+    /// Appends the observations of the following spots
+    ///
+    /// Segments keep their type unless the following spots observed them as well.
+    fn extend(&mut self, other: Self) {
+        self.resize(other.types.len());
+        for (sid, ty) in other.types.into_iter().enumerate() {
+            if ty.is_some() {
+                self.types[sid] = ty;
+            }
+        }
+        for (sid, lengths) in other.lengths.into_iter().enumerate() {
+            self.lengths[sid].extend(lengths);
+        }
+        for (sid, qualities) in other.qualities.into_iter().enumerate() {
+            self.qualities[sid].extend(qualities);
+        }
+    }
+
+    /// Types of the segments (unobserved segments are reported as technical)
+    fn segment_types(&self) -> Vec<SegmentType> {
+        self.types
+            .iter()
+            .map(|ty| ty.unwrap_or(SegmentType::Technical))
+            .collect()
+    }
+}
+
+pub fn describe_inner(
+    accession: &str,
+    skip: usize,
+    limit: usize,
+    num_threads: u64,
+) -> Result<DescribeStats> {
     let reader = SraReader::new(accession)?;
     let num_spots = reader.stop();
 
     let l_bound = skip.max(1);
     let r_bound = (l_bound + limit).min(num_spots as usize);
+    if l_bound > r_bound {
+        bail!("Provided skip ({skip}) is greater than the number of spots ({num_spots})");
+    }
 
-    let mut num_segments = 0;
-    let mut segment_types = Vec::new();
-    let mut segment_lengths = Vec::new();
-    let mut segment_qualities = Vec::new();
-    for record in reader.into_range_iter(l_bound as i64, r_bound as u64)? {
-        let record = record?;
-        for segment in record.into_iter() {
-            // calculate mean quality
-            let mean_quality = calculate_average_quality(segment.qual());
-
-            // update segment stats
-            num_segments = num_segments.max(segment.sid());
-
-            if segment_types.len() <= segment.sid() {
-                segment_types.resize(segment.sid() + 1, SegmentType::Technical);
-                segment_lengths.resize(segment.sid() + 1, Vec::new());
-                segment_qualities.resize(segment.sid() + 1, Vec::new());
+    // Workers pull chunks of spots and observe their segments
+    let queue = Arc::new(SpotQueue::new(
+        &[SpotRange::new(l_bound as u64, r_bound as u64)],
+        SPOT_CHUNK_SIZE,
+    ));
+    let mut handles = Vec::new();
+    for _ in 0..queue.num_workers(num_threads) {
+        let accession = accession.to_string();
+        let queue = queue.clone();
+        let handle = std::thread::spawn(move || -> Result<Vec<(usize, SegmentObservations)>> {
+            let mut observed = Vec::new();
+            let reader = SraReader::new(&accession)?;
+            while let Some((chunk_idx, SpotRange { start, stop })) = queue.next_chunk() {
+                let mut observations = SegmentObservations::default();
+                for record in reader.into_range_iter(start as i64, stop)? {
+                    let record = record?;
+                    for segment in record.into_iter() {
+                        observations.observe(
+                            segment.sid(),
+                            segment.ty(),
+                            segment.len(),
+                            segment.qual(),
+                        );
+                    }
+                }
+                observed.push((chunk_idx, observations));
             }
-            segment_types[segment.sid()] = segment.ty();
-            segment_lengths[segment.sid()].push(segment.len() as f64);
-            segment_qualities[segment.sid()].push(mean_quality);
-        }
+            Ok(observed)
+        });
+        handles.push(handle);
+    }
+
+    // Merge the observations in spot order
+    let mut observed = Vec::new();
+    for handle in handles {
+        observed.extend(handle.join().expect("Thread panicked")?);
+    }
+    observed.sort_unstable_by_key(|(chunk_idx, _)| *chunk_idx);
+    let mut observations = SegmentObservations::default();
+    for (_, chunk_observations) in observed {
+        observations.extend(chunk_observations);
     }
 
     let stats = DescribeStats::new(
-        segment_types,
-        segment_lengths,
-        segment_qualities,
+        observations.segment_types(),
+        observations.lengths,
+        observations.qualities,
         r_bound - l_bound,
         l_bound,
         r_bound,
@@ -81,7 +159,7 @@ pub fn describe(input: &InputOptions, opts: &DescribeOptions) -> Result<()> {
     } else {
         input.accession.to_string()
     };
-    let stats = describe_inner(&accession, opts.skip, opts.limit)?;
+    let stats = describe_inner(&accession, opts.skip, opts.limit, opts.threads())?;
     stats.pprint(&mut std::io::stdout())?;
 
     Ok(())
@@ -92,6 +170,45 @@ mod tests {
     use super::*;
     use tempfile::NamedTempFile;
 
+    // SegmentObservations::extend tests
+    #[test]
+    fn segment_observations_extend_keeps_observed_types() {
+        let mut first = SegmentObservations::default();
+        first.observe(0, SegmentType::Biological, 4, b"IIII");
+        first.observe(2, SegmentType::Biological, 4, b"IIII");
+
+        // the following spots only observed the first two segments
+        let mut second = SegmentObservations::default();
+        second.observe(0, SegmentType::Technical, 2, b"II");
+        second.observe(1, SegmentType::Biological, 2, b"II");
+        first.extend(second);
+
+        assert_eq!(
+            first.segment_types(),
+            vec![
+                SegmentType::Technical,
+                SegmentType::Biological,
+                SegmentType::Biological
+            ]
+        );
+        assert_eq!(first.lengths, vec![vec![4.0, 2.0], vec![2.0], vec![4.0]]);
+
+        // segments observed by neither side are reported as technical
+        let mut third = SegmentObservations::default();
+        third.observe(4, SegmentType::Biological, 1, b"I");
+        first.extend(third);
+        assert_eq!(
+            first.segment_types(),
+            vec![
+                SegmentType::Technical,
+                SegmentType::Biological,
+                SegmentType::Biological,
+                SegmentType::Technical,
+                SegmentType::Biological
+            ]
+        );
+    }
+
     // describe_inner tests
     #[test]
     fn describe_inner_handles_invalid_sra_file() {
@@ -100,7 +217,7 @@ mod tests {
         let temp_sra = NamedTempFile::new().unwrap();
         let path = temp_sra.path().to_str().unwrap();
 
-        let result = describe_inner(path, 0, 10);
+        let result = describe_inner(path, 0, 10, 1);
 
         assert!(
             result.is_err(),
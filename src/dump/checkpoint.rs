@@ -0,0 +1,335 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+
+use super::output::SegmentWriter;
+use crate::cli::{DumpOutput, FilterOptions, QualityOptions};
+use crate::output::{build_append_writer, BoxedWriter, Codec};
+use crate::spots::{SpotList, SpotRange};
+
+/// Number of spots written between two checkpoints
+const CHECKPOINT_INTERVAL: u64 = 1024 * 1024;
+
+/// Progress of a dump which can be resumed after an interruption
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Accession the outputs were written from
+    pub accession: String,
+    /// Options which determine the written records (see [`output_settings`])
+    pub settings: BTreeMap<String, String>,
+    /// Spot ranges which were completely written to the outputs
+    pub completed: Vec<SpotRange>,
+    /// Size (in bytes) of each output file once the completed spots were written
+    pub outputs: BTreeMap<String, u64>,
+}
+impl Checkpoint {
+    pub fn new(accession: &str, settings: BTreeMap<String, String>) -> Self {
+        Self {
+            accession: accession.to_string(),
+            settings,
+            ..Default::default()
+        }
+    }
+
+    /// Loads the checkpoint of a previous run (or starts a new one if there is none)
+    ///
+    /// The run must use the same output settings, as its records would otherwise be
+    /// appended to outputs holding a different selection or encoding.
+    pub fn resume(path: &str, accession: &str, settings: BTreeMap<String, String>) -> Result<Self> {
+        if !Path::new(path).exists() {
+            eprintln!("Warning: No checkpoint found at {path}. Starting from the first spot.");
+            return Ok(Self::new(accession, settings));
+        }
+        let checkpoint: Self = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        if checkpoint.accession != accession {
+            bail!(
+                "Checkpoint {path} belongs to accession {} (not {accession})",
+                checkpoint.accession
+            );
+        }
+        for (option, value) in &settings {
+            let previous = checkpoint.settings.get(option);
+            if previous != Some(value) {
+                bail!(
+                    "Checkpoint {path} was written with {option} {}, but this run uses {value}",
+                    previous.map_or("(unset)", String::as_str)
+                );
+            }
+        }
+        Ok(checkpoint)
+    }
+
+    /// Writes the checkpoint through a temporary file so that it is replaced atomically
+    pub fn save(&self, path: &str) -> Result<()> {
+        let tmp_path = format!("{path}.tmp");
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.into_inner()?.sync_all()?;
+        std::fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Spots which do not need to be processed again
+    pub fn completed(&self) -> SpotList {
+        SpotList::from_ranges(self.completed.clone())
+    }
+}
+
+/// Options which determine the records written to the outputs (keyed by their flag)
+pub fn output_settings(
+    output_opts: &DumpOutput,
+    filter_opts: &FilterOptions,
+    quality_opts: &QualityOptions,
+) -> BTreeMap<String, String> {
+    fn value<T: Debug>(value: &Option<T>) -> String {
+        value
+            .as_ref()
+            .map_or("(unset)".to_string(), |value| format!("{value:?}"))
+    }
+    let trim_opts = &filter_opts.trim;
+    [
+        ("--include", format!("{:?}", filter_opts.include)),
+        ("--format", format!("{:?}", output_opts.format)),
+        ("--compression", format!("{:?}", output_opts.compression)),
+        ("--compression-level", value(&output_opts.compression_level)),
+        ("--defline", format!("{:?}", output_opts.defline)),
+        ("--defline-seq", value(&output_opts.defline_seq)),
+        ("--defline-qual", value(&output_opts.defline_qual)),
+        ("--split", output_opts.split.to_string()),
+        ("--split-3", output_opts.split3.to_string()),
+        ("--tech-tag", value(&output_opts.tech_tag)),
+        ("--min-read-len", filter_opts.min_read_len.to_string()),
+        ("--skip-technical", filter_opts.skip_technical.to_string()),
+        ("--max-n-frac", value(&filter_opts.max_n_frac)),
+        ("--min-mean-qual", value(&filter_opts.min_mean_qual)),
+        ("--max-ee", value(&filter_opts.max_ee)),
+        ("--max-dust", value(&filter_opts.max_dust)),
+        ("--spot-filter", format!("{:?}", filter_opts.spot_filter)),
+        ("--start", value(&filter_opts.start)),
+        ("--stop", value(&filter_opts.stop)),
+        ("--limit", value(&filter_opts.limit)),
+        ("--sample-frac", value(&filter_opts.sample_frac)),
+        ("--sample-count", value(&filter_opts.sample_count)),
+        ("--seed", filter_opts.seed.to_string()),
+        ("--spot-list", value(&filter_opts.spot_list)),
+        ("--trim-window", value(&trim_opts.trim_window)),
+        ("--trim-leading", value(&trim_opts.trim_leading)),
+        ("--trim-trailing", value(&trim_opts.trim_trailing)),
+        ("--trim-trailing-n", trim_opts.trim_trailing_n.to_string()),
+        ("--qual-bin", quality_opts.qual_bin.to_string()),
+        ("--qual-fixed", value(&quality_opts.qual_fixed)),
+        ("--qual-cap", value(&quality_opts.qual_cap)),
+        ("--phred64", quality_opts.phred64.to_string()),
+    ]
+    .into_iter()
+    .map(|(option, value)| (option.to_string(), value))
+    .collect()
+}
+
+/// A writer struct which periodically records the written spots in a [`Checkpoint`].
+///
+/// Before each checkpoint all compression streams are finished, so that every output file
+/// ends on a complete gzip/zstd member. Output files are truncated to their checkpointed
+/// size when reopened, which drops records written after the last checkpoint.
+pub struct CheckpointWriter {
+    /// Output paths in handle order (`None` for handles without a file)
+    paths: Vec<Option<String>>,
+    codec: Codec,
+    num_threads: usize,
+    /// Handles opened since the last checkpoint
+    handles: Vec<Option<BoxedWriter>>,
+    checkpoint: Checkpoint,
+    checkpoint_path: String,
+    /// Spots written since the last checkpoint
+    pending: Vec<SpotRange>,
+    num_pending: u64,
+}
+impl CheckpointWriter {
+    pub fn new(
+        paths: Vec<Option<String>>,
+        codec: Codec,
+        num_threads: usize,
+        checkpoint: Checkpoint,
+        checkpoint_path: &str,
+    ) -> Result<Self> {
+        let mut writer = Self {
+            handles: (0..paths.len()).map(|_| None).collect(),
+            paths,
+            codec,
+            num_threads,
+            checkpoint,
+            checkpoint_path: checkpoint_path.to_string(),
+            pending: Vec::new(),
+            num_pending: 0,
+        };
+        // Reset all outputs to their checkpointed state
+        for path in writer.paths.iter().flatten() {
+            let size = writer.checkpoint.outputs.get(path).copied().unwrap_or(0);
+            OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(path)?
+                .set_len(size)?;
+        }
+        writer.save()?;
+        Ok(writer)
+    }
+
+    /// Reopens an output after its last checkpointed member
+    fn open(&mut self, idx: usize) -> Result<()> {
+        let Some(path) = &self.paths[idx] else {
+            bail!("Received reads for an output without a file (handle {idx})");
+        };
+        let size = self.checkpoint.outputs.get(path).copied().unwrap_or(0);
+        let writer = build_append_writer(path, size, self.codec, self.num_threads)?;
+        self.handles[idx] = Some(writer);
+        Ok(())
+    }
+
+    /// Finish all compression streams and record the written spots
+    ///
+    /// Output sizes are only recorded once their streams were finished without errors.
+    fn save(&mut self) -> Result<()> {
+        for handle in self.handles.iter_mut() {
+            if let Some(handle) = handle.take() {
+                handle.finish()?;
+            }
+        }
+        for path in self.paths.iter().flatten() {
+            let size = std::fs::metadata(path)?.len();
+            self.checkpoint.outputs.insert(path.clone(), size);
+        }
+        self.checkpoint.completed.append(&mut self.pending);
+        self.checkpoint.completed = self.checkpoint.completed().ranges().to_vec();
+        self.num_pending = 0;
+        self.checkpoint.save(&self.checkpoint_path)
+    }
+}
+impl SegmentWriter for CheckpointWriter {
+    fn num_segments(&self) -> usize {
+        self.paths.len()
+    }
+
+    fn write_all_buffers(&mut self, buffers: &mut [Vec<u8>], counts: &mut [usize]) -> Result<()> {
+        for (idx, (local_buf, local_count)) in buffers.iter_mut().zip(counts.iter_mut()).enumerate()
+        {
+            // Skip writing empty segments
+            if *local_count == 0 {
+                continue;
+            }
+            if self.handles[idx].is_none() {
+                self.open(idx)?;
+            }
+            if let Some(handle) = &mut self.handles[idx] {
+                handle.write_all(local_buf.drain(..).as_slice())?;
+                handle.flush()?;
+            }
+            *local_count = 0;
+        }
+        Ok(())
+    }
+
+    fn mark_spots(&mut self, spots: &[SpotRange]) -> Result<()> {
+        self.pending.extend_from_slice(spots);
+        self.num_pending += spots.iter().map(SpotRange::len).sum::<u64>();
+        if self.num_pending >= CHECKPOINT_INTERVAL {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Compression;
+    use tempfile::TempDir;
+
+    fn settings() -> BTreeMap<String, String> {
+        BTreeMap::from([
+            ("--compression".to_string(), "Gzip".to_string()),
+            ("--include".to_string(), "[0, 1]".to_string()),
+        ])
+    }
+
+    // CheckpointWriter tests
+    #[test]
+    fn checkpoint_writer_discards_unrecorded_records() {
+        let temp_dir = TempDir::new().unwrap();
+        let output = temp_dir.path().join("seg_0.fa");
+        let output = output.to_str().unwrap().to_string();
+        let checkpoint_path = temp_dir.path().join("checkpoint.json");
+        let checkpoint_path = checkpoint_path.to_str().unwrap();
+
+        let mut writer = CheckpointWriter::new(
+            vec![Some(output.clone())],
+            Compression::Uncompressed.into(),
+            1,
+            Checkpoint::new("SRR0", settings()),
+            checkpoint_path,
+        )
+        .unwrap();
+        writer
+            .write_all_buffers(&mut [b">1\nA\n".to_vec()], &mut [1])
+            .unwrap();
+        writer.mark_spots(&[SpotRange::new(1, 1)]).unwrap();
+        writer.finish().unwrap();
+
+        // records written after the last checkpoint are lost on interruption
+        writer
+            .write_all_buffers(&mut [b">2\nC\n".to_vec()], &mut [1])
+            .unwrap();
+        drop(writer);
+
+        let checkpoint = Checkpoint::resume(checkpoint_path, "SRR0", settings()).unwrap();
+        assert_eq!(checkpoint.completed, vec![SpotRange::new(1, 1)]);
+        assert!(Checkpoint::resume(checkpoint_path, "SRR1", settings()).is_err());
+
+        let mut writer = CheckpointWriter::new(
+            vec![Some(output.clone())],
+            Compression::Uncompressed.into(),
+            1,
+            checkpoint,
+            checkpoint_path,
+        )
+        .unwrap();
+        writer
+            .write_all_buffers(&mut [b">2\nG\n".to_vec()], &mut [1])
+            .unwrap();
+        writer.mark_spots(&[SpotRange::new(2, 2)]).unwrap();
+        writer.finish().unwrap();
+
+        assert_eq!(std::fs::read_to_string(&output).unwrap(), ">1\nA\n>2\nG\n");
+        let checkpoint = Checkpoint::resume(checkpoint_path, "SRR0", settings()).unwrap();
+        assert_eq!(checkpoint.completed, vec![SpotRange::new(1, 2)]);
+    }
+
+    // Checkpoint::resume tests
+    #[test]
+    fn checkpoint_resume_rejects_changed_settings() {
+        let temp_dir = TempDir::new().unwrap();
+        let checkpoint_path = temp_dir.path().join("checkpoint.json");
+        let checkpoint_path = checkpoint_path.to_str().unwrap();
+        Checkpoint::new("SRR0", settings())
+            .save(checkpoint_path)
+            .unwrap();
+
+        let mut changed = settings();
+        changed.insert("--compression".to_string(), "Zstd".to_string());
+        let err = Checkpoint::resume(checkpoint_path, "SRR0", changed).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("was written with --compression Gzip, but this run uses Zstd"));
+        assert!(Checkpoint::resume(checkpoint_path, "SRR0", settings()).is_ok());
+    }
+}
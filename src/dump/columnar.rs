@@ -0,0 +1,356 @@
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use arrow::array::{
+    Array, ArrayBuilder, ArrayRef, ListArray, StringBuilder, UInt32Builder, UInt64Builder,
+    UInt8Builder,
+};
+use arrow::buffer::OffsetBuffer;
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::ipc::writer::{IpcWriteOptions, StreamWriter};
+use arrow::ipc::CompressionType;
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression as ParquetCompression, GzipLevel, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+
+use super::defline::Read;
+use super::output::{BoxedSegmentWriter, SegmentWriter};
+use crate::cli::{ColumnarRows, FilterOptions, OutputFormat};
+use crate::output::{build_named_writers, build_writers, BoxedWriter, Codec, Compression};
+use crate::partial::PartialOutputs;
+
+/// Builds the output schema
+///
+/// Per-spot rows hold the segment columns as lists (one element per segment).
+fn build_schema(rows: ColumnarRows) -> SchemaRef {
+    let segment_columns = [
+        ("segment_id", DataType::UInt8),
+        ("segment_type", DataType::Utf8),
+        ("sequence", DataType::Utf8),
+        ("quality", DataType::Utf8),
+        ("length", DataType::UInt32),
+    ];
+    let mut fields = vec![Field::new("spot_id", DataType::UInt64, false)];
+    for (name, data_type) in segment_columns {
+        let data_type = match rows {
+            ColumnarRows::Segment => data_type,
+            ColumnarRows::Spot => DataType::List(Arc::new(Field::new("item", data_type, true))),
+        };
+        fields.push(Field::new(name, data_type, false));
+    }
+    Arc::new(Schema::new(fields))
+}
+
+/// Builders of the segment columns (one value per segment)
+#[derive(Default)]
+struct SegmentColumns {
+    segment_id: UInt8Builder,
+    segment_type: StringBuilder,
+    sequence: StringBuilder,
+    quality: StringBuilder,
+    length: UInt32Builder,
+}
+impl SegmentColumns {
+    fn append(&mut self, read: &Read<'_>, is_technical: bool) -> Result<()> {
+        self.segment_id.append_value(read.sid as u8);
+        self.segment_type.append_value(if is_technical {
+            "technical"
+        } else {
+            "biological"
+        });
+        self.sequence.append_value(std::str::from_utf8(read.seq)?);
+        self.quality.append_value(std::str::from_utf8(read.qual)?);
+        self.length.append_value(read.seq.len() as u32);
+        Ok(())
+    }
+
+    fn len(&self) -> usize {
+        self.segment_id.len()
+    }
+
+    /// Takes the built columns and resets the builders
+    fn finish(&mut self) -> Vec<ArrayRef> {
+        vec![
+            Arc::new(self.segment_id.finish()),
+            Arc::new(self.segment_type.finish()),
+            Arc::new(self.sequence.finish()),
+            Arc::new(self.quality.finish()),
+            Arc::new(self.length.finish()),
+        ]
+    }
+}
+
+/// Builds the record batches of the spots processed on a worker thread
+///
+/// Only finished batches are handed to the (shared) [`ColumnarWriter`].
+pub struct ColumnarBatch {
+    schema: SchemaRef,
+    rows: ColumnarRows,
+    spot_id: UInt64Builder,
+    segments: SegmentColumns,
+    /// Offsets of the spots into the segment columns (per-spot rows only)
+    offsets: Vec<i32>,
+}
+impl ColumnarBatch {
+    pub fn new(rows: ColumnarRows) -> Self {
+        Self {
+            schema: build_schema(rows),
+            rows,
+            spot_id: UInt64Builder::new(),
+            segments: SegmentColumns::default(),
+            offsets: vec![0],
+        }
+    }
+
+    /// Appends a passing segment of the current spot
+    pub fn append(&mut self, read: &Read<'_>, is_technical: bool) -> Result<()> {
+        self.segments.append(read, is_technical)?;
+        if self.rows == ColumnarRows::Segment {
+            self.spot_id.append_value(read.spot_id);
+        }
+        Ok(())
+    }
+
+    /// Closes the current spot (spots without any passing segments have no row)
+    pub fn end_spot(&mut self, spot_id: u64) {
+        let end = self.segments.len() as i32;
+        if self.rows == ColumnarRows::Spot && self.offsets.last() != Some(&end) {
+            self.spot_id.append_value(spot_id);
+            self.offsets.push(end);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spot_id.is_empty()
+    }
+
+    /// Takes the record batch of the appended spots and starts a new one
+    pub fn finish(&mut self) -> Result<RecordBatch> {
+        let mut columns: Vec<ArrayRef> = vec![Arc::new(self.spot_id.finish())];
+        match self.rows {
+            ColumnarRows::Segment => columns.extend(self.segments.finish()),
+            ColumnarRows::Spot => {
+                let offsets =
+                    OffsetBuffer::new(std::mem::replace(&mut self.offsets, vec![0]).into());
+                for values in self.segments.finish() {
+                    let field = Arc::new(Field::new("item", values.data_type().clone(), true));
+                    columns.push(Arc::new(ListArray::try_new(
+                        field,
+                        offsets.clone(),
+                        values,
+                        None,
+                    )?));
+                }
+            }
+        }
+        Ok(RecordBatch::try_new(self.schema.clone(), columns)?)
+    }
+}
+
+/// Column codec of parquet outputs
+fn parquet_compression(compression: Codec) -> Result<ParquetCompression> {
+    Ok(match compression.compression {
+        Compression::Uncompressed => ParquetCompression::UNCOMPRESSED,
+        Compression::Gzip | Compression::Bgzip => {
+            ParquetCompression::GZIP(match compression.level {
+                Some(level) => GzipLevel::try_new(level as u32)?,
+                None => GzipLevel::default(),
+            })
+        }
+        Compression::Zstd => ParquetCompression::ZSTD(match compression.level {
+            Some(level) => ZstdLevel::try_new(level)?,
+            None => ZstdLevel::default(),
+        }),
+        Compression::Lz4 => ParquetCompression::LZ4_RAW,
+        Compression::Xz | Compression::Bzip2 => {
+            bail!("Parquet only supports gzip, zstd and lz4 compression")
+        }
+    })
+}
+
+/// Name of the column codec of parquet outputs (as recorded in manifests)
+pub fn parquet_codec_name(compression: Codec) -> Result<&'static str> {
+    Ok(match parquet_compression(compression)? {
+        ParquetCompression::GZIP(_) => "gzip",
+        ParquetCompression::ZSTD(_) => "zstd",
+        ParquetCompression::LZ4_RAW => "lz4_raw",
+        _ => "none",
+    })
+}
+
+/// The columnar encoder owning the output handle
+enum ColumnarSink {
+    Parquet(ArrowWriter<BoxedWriter>),
+    Ipc(StreamWriter<BoxedWriter>),
+}
+
+/// A Writer struct which encodes the record batches built on the worker threads
+pub struct ColumnarWriter {
+    /// Taken on finish (the parquet footer is written on close)
+    sink: Option<ColumnarSink>,
+}
+
+impl ColumnarWriter {
+    pub fn new(
+        handle: BoxedWriter,
+        format: OutputFormat,
+        rows: ColumnarRows,
+        compression: Codec,
+    ) -> Result<Self> {
+        let schema = build_schema(rows);
+        let sink = match format {
+            OutputFormat::Parquet => {
+                let codec = parquet_compression(compression)?;
+                let props = WriterProperties::builder().set_compression(codec).build();
+                ColumnarSink::Parquet(ArrowWriter::try_new(handle, schema.clone(), Some(props))?)
+            }
+            OutputFormat::ArrowIpc => {
+                let codec = match compression.compression {
+                    Compression::Uncompressed => None,
+                    Compression::Zstd => Some(CompressionType::ZSTD),
+                    Compression::Lz4 => Some(CompressionType::LZ4_FRAME),
+                    _ => bail!("Arrow IPC streams only support zstd and lz4 compression"),
+                };
+                let options = IpcWriteOptions::default().try_with_compression(codec)?;
+                ColumnarSink::Ipc(StreamWriter::try_new_with_options(
+                    handle, &schema, options,
+                )?)
+            }
+            _ => bail!("{format:?} is not a columnar output format"),
+        };
+        Ok(Self { sink: Some(sink) })
+    }
+}
+
+impl SegmentWriter for ColumnarWriter {
+    fn num_segments(&self) -> usize {
+        1
+    }
+
+    fn write_all_buffers(&mut self, _buffers: &mut [Vec<u8>], counts: &mut [usize]) -> Result<()> {
+        if counts.iter().any(|&count| count > 0) {
+            bail!("Columnar outputs are only written as record batches");
+        }
+        Ok(())
+    }
+
+    fn write_batch(&mut self, batch: RecordBatch) -> Result<()> {
+        match self.sink.as_mut() {
+            Some(ColumnarSink::Parquet(writer)) => writer.write(&batch)?,
+            Some(ColumnarSink::Ipc(writer)) => writer.write(&batch)?,
+            None => bail!("Columnar writer was already finished"),
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let handle = match self.sink.take() {
+            Some(ColumnarSink::Parquet(writer)) => writer.into_inner()?,
+            Some(ColumnarSink::Ipc(writer)) => writer.into_inner()?,
+            None => return Ok(()),
+        };
+        handle.finish()
+    }
+}
+
+/// Builds the columnar writer
+///
+/// Parquet is written to <outdir>/<accession>.parquet and Arrow IPC is streamed to stdout.
+pub fn build_columnar_writer(
+    outdir: &str,
+    accession: &str,
+    format: OutputFormat,
+    rows: ColumnarRows,
+    compression: Codec,
+    filter_opts: &FilterOptions,
+    outputs: &PartialOutputs,
+) -> Result<BoxedSegmentWriter> {
+    // Compression is handled by the columnar encoder
+    let mut handles = if format == OutputFormat::Parquet {
+        build_named_writers(
+            outdir,
+            "",
+            Compression::Uncompressed.into(),
+            format,
+            1,
+            &[Some(accession.to_string())],
+            false,
+            outputs,
+        )?
+    } else {
+        build_writers(
+            None,
+            "",
+            Compression::Uncompressed.into(),
+            format,
+            1,
+            filter_opts,
+            false,
+            outputs,
+        )?
+    };
+    let writer = ColumnarWriter::new(handles.remove(0), format, rows, compression)?;
+    Ok(Box::new(writer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{StringArray, UInt64Array};
+
+    fn build_batch(rows: ColumnarRows) -> RecordBatch {
+        let mut batch = ColumnarBatch::new(rows);
+        let spots: [(u64, &[(u8, &str)]); 3] =
+            [(1, &[(0, "ACGT"), (1, "GG")]), (2, &[]), (3, &[(0, "TTA")])];
+        for (spot_id, segments) in spots {
+            for &(sid, seq) in segments {
+                let read = Read {
+                    spot_id,
+                    sid: sid as usize,
+                    seq: seq.as_bytes(),
+                    qual: &b"IIII"[..seq.len()],
+                };
+                batch.append(&read, sid == 1).unwrap();
+            }
+            batch.end_spot(spot_id);
+        }
+        assert!(!batch.is_empty());
+        let finished = batch.finish().unwrap();
+        // the builder starts over once a batch was taken
+        assert!(batch.is_empty());
+        finished
+    }
+
+    // ColumnarBatch tests
+    #[test]
+    fn segment_batch_has_one_row_per_segment() {
+        let batch = build_batch(ColumnarRows::Segment);
+        assert_eq!(batch.num_rows(), 3);
+        let segment_type = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(segment_type.value(1), "technical");
+    }
+
+    #[test]
+    fn spot_batch_groups_segments() {
+        let batch = build_batch(ColumnarRows::Spot);
+        assert_eq!(batch.num_rows(), 2);
+        let spot_id = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(spot_id.values(), &[1, 3]);
+        let sequence = batch
+            .column(3)
+            .as_any()
+            .downcast_ref::<ListArray>()
+            .unwrap();
+        assert_eq!(sequence.value(0).len(), 2);
+        assert_eq!(sequence.value(1).len(), 1);
+    }
+}
@@ -0,0 +1,236 @@
+use std::io::Write;
+
+use anyhow::{bail, Result};
+
+use crate::cli::DeflineStyle;
+
+/// A single read as it will be written to the output
+pub struct Read<'a> {
+    /// Spot ID (1-indexed row of the archive)
+    pub spot_id: u64,
+    /// Segment ID (zero-indexed)
+    pub sid: usize,
+    pub seq: &'a [u8],
+    pub qual: &'a [u8],
+}
+
+/// A single element of a parsed defline template
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(String),
+    /// `$ac`
+    Accession,
+    /// `$si`
+    SpotId,
+    /// `$sd`
+    SegmentId,
+    /// `$ri`
+    ReadNumber,
+    /// `$rl`
+    ReadLength,
+}
+impl Token {
+    fn from_placeholder(placeholder: &str) -> Option<Self> {
+        match placeholder {
+            "ac" => Some(Self::Accession),
+            "si" => Some(Self::SpotId),
+            "sd" => Some(Self::SegmentId),
+            "ri" => Some(Self::ReadNumber),
+            "rl" => Some(Self::ReadLength),
+            _ => None,
+        }
+    }
+}
+
+/// Placeholders (and what they stand for) which the archive reader cannot fill
+const UNAVAILABLE_PLACEHOLDERS: [(&str, &str); 2] =
+    [("sn", "original read name"), ("sg", "spot group")];
+
+/// Parses a defline template into its tokens
+///
+/// A leading marker character (`@`, `>`, or `+`) is optional and ignored.
+fn parse_template(template: &str) -> Result<Vec<Token>> {
+    let template = template.strip_prefix(['@', '>', '+']).unwrap_or(template);
+
+    let mut tokens = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.char_indices();
+    while let Some((idx, c)) = chars.next() {
+        if c != '$' {
+            literal.push(c);
+            continue;
+        }
+        let placeholder = template.get(idx + 1..idx + 3);
+        if let Some((name, desc)) = UNAVAILABLE_PLACEHOLDERS
+            .iter()
+            .find(|(name, _)| placeholder == Some(*name))
+        {
+            bail!("Placeholder ${name} ({desc}) is not available from this archive reader: {template}");
+        }
+        let Some(token) = placeholder.and_then(Token::from_placeholder) else {
+            bail!("Invalid placeholder in defline template at position {idx}: {template}");
+        };
+        if !literal.is_empty() {
+            tokens.push(Token::Literal(std::mem::take(&mut literal)));
+        }
+        tokens.push(token);
+        chars.nth(1);
+    }
+    if !literal.is_empty() {
+        tokens.push(Token::Literal(literal));
+    }
+    Ok(tokens)
+}
+
+/// Renders the sequence and quality deflines of a read
+#[derive(Debug, Clone)]
+pub struct Defline {
+    seq: Vec<Token>,
+    qual: Vec<Token>,
+    /// Accession name used by the `$ac` placeholder
+    accession: String,
+}
+impl Defline {
+    /// Builds the deflines from a style, optionally overriding either line with a template
+    pub fn new(
+        style: DeflineStyle,
+        seq_template: Option<&str>,
+        qual_template: Option<&str>,
+        accession: &str,
+    ) -> Result<Self> {
+        let (style_seq, style_qual) = match style {
+            DeflineStyle::Xsra => ("$si.$sd", ""),
+            // Original read names are not exposed by the reader so the spot ID is
+            // used in their place (as fastq-dump does for nameless runs)
+            DeflineStyle::FastqDump => ("$ac.$si $si length=$rl", "$ac.$si $si length=$rl"),
+            DeflineStyle::FasterqDump => ("$ac.$si $si length=$rl", ""),
+            DeflineStyle::Original => {
+                bail!("The orig defline style requires the original read names, which are not available from this archive reader")
+            }
+        };
+        Ok(Self {
+            seq: parse_template(seq_template.unwrap_or(style_seq))?,
+            qual: parse_template(qual_template.unwrap_or(style_qual))?,
+            accession: accession.to_string(),
+        })
+    }
+
+    /// Write the sequence defline (including the leading marker and trailing newline)
+    pub fn write_seq<W: Write>(&self, wtr: &mut W, marker: char, read: &Read<'_>) -> Result<()> {
+        write!(wtr, "{marker}")?;
+        self.write_tokens(wtr, &self.seq, read)?;
+        writeln!(wtr)?;
+        Ok(())
+    }
+
+    /// Write the quality defline (including the leading `+` and trailing newline)
+    pub fn write_qual<W: Write>(&self, wtr: &mut W, read: &Read<'_>) -> Result<()> {
+        write!(wtr, "+")?;
+        self.write_tokens(wtr, &self.qual, read)?;
+        writeln!(wtr)?;
+        Ok(())
+    }
+
+    fn write_tokens<W: Write>(&self, wtr: &mut W, tokens: &[Token], read: &Read<'_>) -> Result<()> {
+        for token in tokens {
+            match token {
+                Token::Literal(literal) => wtr.write_all(literal.as_bytes())?,
+                Token::Accession => wtr.write_all(self.accession.as_bytes())?,
+                Token::SpotId => write!(wtr, "{}", read.spot_id)?,
+                Token::SegmentId => write!(wtr, "{}", read.sid)?,
+                Token::ReadNumber => write!(wtr, "{}", read.sid + 1)?,
+                Token::ReadLength => write!(wtr, "{}", read.seq.len())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(defline: &Defline) -> String {
+        let read = Read {
+            spot_id: 7,
+            sid: 1,
+            seq: b"ACGT",
+            qual: b"IIII",
+        };
+        let mut buffer = Vec::new();
+        defline.write_seq(&mut buffer, '@', &read).unwrap();
+        defline.write_qual(&mut buffer, &read).unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+
+    fn render_style(style: DeflineStyle) -> String {
+        render(&Defline::new(style, None, None, "SRR123").unwrap())
+    }
+
+    #[test]
+    fn defline_xsra_style() {
+        assert_eq!(render_style(DeflineStyle::Xsra), "@7.1\n+\n");
+    }
+
+    #[test]
+    fn defline_fastq_dump_style() {
+        assert_eq!(
+            render_style(DeflineStyle::FastqDump),
+            "@SRR123.7 7 length=4\n+SRR123.7 7 length=4\n"
+        );
+    }
+
+    #[test]
+    fn defline_fasterq_dump_style() {
+        assert_eq!(
+            render_style(DeflineStyle::FasterqDump),
+            "@SRR123.7 7 length=4\n+\n"
+        );
+    }
+
+    #[test]
+    fn defline_original_style_is_rejected() {
+        let err = Defline::new(DeflineStyle::Original, None, None, "SRR123").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("not available from this archive reader"));
+    }
+
+    // parse_template tests
+    #[test]
+    fn defline_custom_templates_override_style() {
+        let defline = Defline::new(
+            DeflineStyle::Xsra,
+            Some("@$ac:$si/$ri len=$rl"),
+            Some("+$ac"),
+            "SRR123",
+        )
+        .unwrap();
+        assert_eq!(render(&defline), "@SRR123:7/2 len=4\n+SRR123\n");
+    }
+
+    #[test]
+    fn parse_template_rejects_unknown_placeholder() {
+        assert!(parse_template("$ac.$xx").is_err());
+        assert!(parse_template("$a").is_err());
+    }
+
+    #[test]
+    fn parse_template_rejects_unavailable_placeholders() {
+        // original read names and spot groups are not available from the reader
+        for template in ["$ac.$sn", "$ac.$sg"] {
+            let err = parse_template(template).unwrap_err();
+            assert!(err
+                .to_string()
+                .contains("not available from this archive reader"));
+        }
+    }
+
+    #[test]
+    fn parse_template_handles_literals() {
+        assert_eq!(
+            parse_template("read_$si").unwrap(),
+            vec![Token::Literal("read_".to_string()), Token::SpotId]
+        );
+    }
+}
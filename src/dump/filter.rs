@@ -0,0 +1,160 @@
+use crate::cli::FilterOptions;
+use crate::quality::PHRED33_OFFSET;
+
+/// Reasons a read may fail the read filters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterReason {
+    /// Shorter than the minimum read length
+    Size,
+    /// Above the maximum fraction of N bases
+    NContent,
+    /// Below the minimum mean quality score
+    MeanQuality,
+    /// Above the maximum number of expected errors
+    ExpectedErrors,
+    /// Above the maximum DUST score
+    LowComplexity,
+}
+
+/// Checks a read against the read filters and returns the first failed filter
+///
+/// Quality-based filters are skipped for reads without quality scores.
+pub fn check_read(filter_opts: &FilterOptions, seq: &[u8], qual: &[u8]) -> Option<FilterReason> {
+    if seq.len() < filter_opts.min_read_len {
+        return Some(FilterReason::Size);
+    }
+    if seq.is_empty() {
+        return None;
+    }
+    if let Some(max_n_frac) = filter_opts.max_n_frac {
+        if n_fraction(seq) > max_n_frac {
+            return Some(FilterReason::NContent);
+        }
+    }
+    if qual.len() == seq.len() {
+        if let Some(min_mean_qual) = filter_opts.min_mean_qual {
+            if mean_quality(qual) < min_mean_qual {
+                return Some(FilterReason::MeanQuality);
+            }
+        }
+        if let Some(max_ee) = filter_opts.max_ee {
+            if expected_errors(qual) > max_ee {
+                return Some(FilterReason::ExpectedErrors);
+            }
+        }
+    }
+    if let Some(max_dust) = filter_opts.max_dust {
+        if dust_score(seq) > max_dust {
+            return Some(FilterReason::LowComplexity);
+        }
+    }
+    None
+}
+
+/// Fraction of ambiguous (N) bases
+fn n_fraction(seq: &[u8]) -> f64 {
+    let num_n = seq.iter().filter(|b| b.eq_ignore_ascii_case(&b'N')).count();
+    num_n as f64 / seq.len() as f64
+}
+
+/// Arithmetic mean of the Phred scores
+fn mean_quality(qual: &[u8]) -> f64 {
+    let total: u64 = qual
+        .iter()
+        .map(|q| q.saturating_sub(PHRED33_OFFSET) as u64)
+        .sum();
+    total as f64 / qual.len() as f64
+}
+
+/// Expected number of errors (sum of the per-base error probabilities)
+fn expected_errors(qual: &[u8]) -> f64 {
+    qual.iter()
+        .map(|q| 10f64.powf(-(q.saturating_sub(PHRED33_OFFSET) as f64) / 10.0))
+        .sum()
+}
+
+/// DUST score of the whole read
+///
+/// Sums c(c-1)/2 over the counts of all 64 triplets and normalizes by the number
+/// of triplets minus one. Reads with fewer than 2 triplets score 0.
+fn dust_score(seq: &[u8]) -> f64 {
+    if seq.len() < 4 {
+        return 0.0;
+    }
+    let mut counts = [0u32; 64];
+    let mut num_triplets = 0;
+    for triplet in seq.windows(3) {
+        let mut idx = 0;
+        let mut valid = true;
+        for base in triplet {
+            let code = match base.to_ascii_uppercase() {
+                b'A' => 0,
+                b'C' => 1,
+                b'G' => 2,
+                b'T' => 3,
+                _ => {
+                    valid = false;
+                    break;
+                }
+            };
+            idx = (idx << 2) | code;
+        }
+        // triplets containing ambiguous bases are not counted
+        if valid {
+            counts[idx] += 1;
+            num_triplets += 1;
+        }
+    }
+    if num_triplets < 2 {
+        return 0.0;
+    }
+    let score: u64 = counts
+        .iter()
+        .map(|&c| c as u64 * (c as u64).saturating_sub(1) / 2)
+        .sum();
+    score as f64 / (num_triplets - 1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // n_fraction tests
+    #[test]
+    fn n_fraction_counts_both_cases() {
+        assert_eq!(n_fraction(b"ACNn"), 0.5);
+        assert_eq!(n_fraction(b"ACGT"), 0.0);
+    }
+
+    // mean_quality tests
+    #[test]
+    fn mean_quality_of_phred33() {
+        // Q40 and Q20
+        assert_eq!(mean_quality(b"I5"), 30.0);
+    }
+
+    // expected_errors tests
+    #[test]
+    fn expected_errors_sums_probabilities() {
+        // Q10 (0.1) + Q20 (0.01)
+        let ee = expected_errors(b"+5");
+        assert!((ee - 0.11).abs() < 1e-9);
+    }
+
+    // dust_score tests
+    #[test]
+    fn dust_score_of_homopolymer() {
+        // 8 identical triplets: 8*7/2 / 7 = 4
+        assert_eq!(dust_score(b"AAAAAAAAAA"), 4.0);
+    }
+
+    #[test]
+    fn dust_score_of_complex_read() {
+        assert_eq!(dust_score(b"ACGTTGCA"), 0.0);
+    }
+
+    #[test]
+    fn dust_score_of_short_read() {
+        assert_eq!(dust_score(b"AAA"), 0.0);
+    }
+}
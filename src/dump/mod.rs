@@ -1,124 +1,649 @@
+mod checkpoint;
+mod columnar;
+mod defline;
+mod filter;
 mod output;
+mod sam;
 mod stats;
+mod trim;
 mod utils;
 
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::ops::Range;
 use std::path::Path;
 use std::sync::Arc;
-
-use anyhow::Result;
-use ncbi_vdb_sys::SraReader;
-use output::{build_segment_writer, BoxedSegmentWriter};
+use std::time::Instant;
+
+use anyhow::{bail, Result};
+use arrow::record_batch::RecordBatch;
+use checkpoint::{output_settings, Checkpoint, CheckpointWriter};
+use columnar::{build_columnar_writer, parquet_codec_name, ColumnarBatch};
+use ncbi_vdb_sys::{Segment, SraReader};
+use output::{build_segment_writer, BoxedSegmentWriter, OrderedWriter, SpotEnds};
 use parking_lot::Mutex;
 
-use crate::cli::{DumpOutput, FilterOptions, InputOptions, OutputFormat};
+use crate::cli::{
+    ColumnarRows, DumpOutput, FilterOptions, InputOptions, OutputFormat, OutputLayout,
+    QualityOptions, RuntimeOptions, SpotFilter,
+};
+use crate::describe::describe_inner;
+use crate::index;
+use crate::manifest::{Manifest, OutputSummary};
 use crate::output::{build_path_name, OutputFileType};
+use crate::partial::PartialOutputs;
 use crate::prefetch::identify_url;
+use crate::progress::{Progress, ProgressLogger};
+use crate::quality::QualityTransform;
+use crate::spots::{SpotList, SpotQueue, SpotRange, SpotSampler, SPOT_CHUNK_SIZE};
 use crate::RECORD_CAPACITY;
 
-use crate::utils::get_num_records;
-use stats::ProcessStatistics;
-use utils::write_segment_to_buffer_set;
+use crate::utils::{accession_name, get_num_records};
+use defline::{Defline, Read};
+use filter::{check_read, FilterReason};
+use sam::{unaligned_flag, SamEncoder, TechTags};
+use stats::{ProcessStatistics, StatsReport};
+use trim::trim_range;
+use utils::write_read_to_buffer_set;
+
+/// Output handle of lone mates in split-3 mode
+const SPLIT3_UNPAIRED: usize = 2;
+
+/// Quality score given to separator bases in concatenated mode
+const CONCAT_SEPARATOR_QUAL: u8 = b'!';
+
+/// A segment selected for output along with its spot context
+struct Selected<'a> {
+    segment: Segment<'a>,
+    /// Mate index of the first two biological segments
+    mate: Option<usize>,
+    /// First failed filter (`None` if the segment passed)
+    verdict: Option<FilterReason>,
+    /// Range of the segment retained after trimming
+    range: Range<usize>,
+}
+impl Selected<'_> {
+    fn sid(&self) -> usize {
+        self.segment.sid()
+    }
+
+    fn seq(&self) -> &[u8] {
+        &self.segment.seq()[self.range.clone()]
+    }
+
+    fn qual(&self) -> &[u8] {
+        let qual = self.segment.qual();
+        qual.get(self.range.clone()).unwrap_or(qual)
+    }
+}
+
+/// Per-spot filtering and formatting logic shared by all worker threads
+struct SpotProcessor {
+    /// Segments included in the output (`None` if all segments are included)
+    segment_set: Option<Vec<usize>>,
+    filter_opts: FilterOptions,
+    format: OutputFormat,
+    layout: OutputLayout,
+    /// Row layout of columnar output
+    rows: ColumnarRows,
+    /// Whether the outputs are split into parts (which requires the spot ends of each batch)
+    chunked: bool,
+    defline: Defline,
+    /// Base inserted between segments in concatenated mode
+    concat_separator: Option<u8>,
+    /// Record encoder for SAM/BAM output
+    sam: Option<SamEncoder>,
+    /// Output-time quality score transform
+    quality: QualityTransform,
+    /// Random spot selection (`None` if all spots are processed)
+    sampler: Option<SpotSampler>,
+    /// Listed spot selection (`None` if all spots are processed)
+    spot_list: Option<SpotList>,
+}
+impl SpotProcessor {
+    fn new(
+        filter_opts: FilterOptions,
+        output_opts: &DumpOutput,
+        quality_opts: &QualityOptions,
+        defline: Defline,
+        accession: &str,
+    ) -> Result<Self> {
+        let segment_set = if filter_opts.include.is_empty() {
+            None
+        } else {
+            // checking a small vector should be faster than a HashSet
+            Some(filter_opts.include.clone())
+        };
+        let concat_separator = match output_opts.concat_separator {
+            Some(sep) if sep.is_ascii_alphabetic() => Some(sep as u8),
+            Some(sep) => bail!("Invalid concatenation separator base: '{sep}'"),
+            None => None,
+        };
+        let format = output_opts.format;
+        if (format.is_sam() || format.is_columnar())
+            && output_opts.layout() != OutputLayout::Interleaved
+        {
+            bail!("{format:?} output is written as a single stream and cannot be combined with --split, --split-3, or --concatenate");
+        }
+        if output_opts.chunk_limits().is_some()
+            && (!output_opts.layout().is_split()
+                || output_opts.named_pipes
+                || output_opts.is_streamed())
+        {
+            bail!("Chunked output (--chunk-reads, --chunk-uncompressed-size) requires --split or --split-3 output files");
+        }
+        if output_opts.is_checkpointed()
+            && (!output_opts.layout().is_split()
+                || output_opts.named_pipes
+                || output_opts.is_streamed()
+                || output_opts.chunk_limits().is_some())
+        {
+            bail!("Checkpoints (--checkpoint, --resume) require --split or --split-3 output files without chunking");
+        }
+        if format.is_columnar() && output_opts.is_streamed() {
+            bail!("{format:?} output cannot be streamed to sockets or file descriptors (--fd, --unix-socket, --tcp)");
+        }
+        if output_opts.chunk_uncompressed_size == Some(0) {
+            bail!("Chunk size must be greater than zero");
+        }
+        if format.is_sam() && quality_opts.phred64 {
+            bail!("SAM/BAM quality scores are always written with a Phred+33 offset");
+        }
+        let sam = if format.is_sam() {
+            Some(SamEncoder::new(
+                output_opts.format == OutputFormat::Bam,
+                accession,
+                output_opts.tech_tag,
+            ))
+        } else {
+            if output_opts.tech_tag.is_some() {
+                bail!("Technical segment tags (--tech-tag) require SAM or BAM output");
+            }
+            None
+        };
+        Ok(Self {
+            segment_set,
+            filter_opts,
+            format: output_opts.format,
+            layout: output_opts.layout(),
+            rows: output_opts.rows,
+            chunked: output_opts.chunk_limits().is_some(),
+            defline,
+            concat_separator,
+            sam,
+            quality: QualityTransform::new(quality_opts)?,
+            sampler: None,
+            spot_list: None,
+        })
+    }
+
+    /// Checks whether a spot is part of the spot list and random sample
+    fn selects(&self, spot_id: u64) -> bool {
+        self.spot_list
+            .as_ref()
+            .is_none_or(|list| list.contains(spot_id))
+            && self.sampler.is_none_or(|sampler| sampler.keep(spot_id))
+    }
+
+    /// Creates the record batch a worker thread fills with columnar rows (if any)
+    fn columnar_batch(&self) -> Option<ColumnarBatch> {
+        self.format
+            .is_columnar()
+            .then(|| ColumnarBatch::new(self.rows))
+    }
+
+    /// Creates the spot ends a worker thread records for its local buffers
+    fn spot_ends(&self) -> SpotEnds {
+        SpotEnds::new(self.chunked)
+    }
+
+    /// Applies the segment filters to a spot and writes the passing segments to the local buffers
+    /// (or to the record batch of columnar output)
+    fn process<'a>(
+        &self,
+        spot_id: u64,
+        segments: impl IntoIterator<Item = Segment<'a>>,
+        local_buffers: &mut [Vec<u8>],
+        counts: &mut [usize],
+        columns: Option<&mut ColumnarBatch>,
+        stats: &mut ProcessStatistics,
+    ) -> Result<()> {
+        // Skip spots which are not listed or outside of the random sample
+        if !self.selects(spot_id) {
+            return Ok(());
+        }
+
+        // Included segments with their mate index, filter result, and trimmed range
+        let mut included = Vec::new();
+        let mut num_mates = 0;
+
+        // Technical segments stored as SAM tags
+        let mut tech = TechTags::default();
+        let store_tech = self
+            .sam
+            .as_ref()
+            .is_some_and(|sam| sam.tech_tag().is_some());
+
+        // Iterate over segments in the record
+        for segment in segments {
+            // Skip segment if outside of set
+            if let Some(ref set) = self.segment_set {
+                if !set.contains(&segment.sid()) {
+                    continue;
+                }
+            }
+
+            // Move technical segments into the SAM tags if required
+            if store_tech && segment.is_technical() {
+                tech.push(segment.seq(), &self.quality.apply(segment.qual()));
+                continue;
+            }
+
+            // Skip technical segments if required
+            if (self.filter_opts.skip_technical || self.layout == OutputLayout::Split3)
+                && segment.is_technical()
+            {
+                // Increment filter statistics
+                stats.inc_filter_type(segment.sid());
+                continue;
+            }
+
+            // The first two biological segments are considered mates
+            let mate = (num_mates < 2).then_some(num_mates);
+            num_mates += 1;
+
+            // Trim the segment ahead of the filters
+            let range = trim_range(&self.filter_opts.trim, segment.seq(), segment.qual());
+            stats.add_trimmed(segment.sid(), segment.seq().len() - range.len());
+
+            let mut selected = Selected {
+                segment,
+                mate,
+                verdict: None,
+                range,
+            };
+            selected.verdict = check_read(&self.filter_opts, selected.seq(), selected.qual());
+            included.push(selected);
+        }
+
+        // Apply the spot-level filter policy
+        let num_failed = included.iter().filter(|s| s.verdict.is_some()).count();
+        let drop_spot = match self.filter_opts.spot_filter {
+            SpotFilter::Segment => false,
+            SpotFilter::Any => num_failed > 0,
+            SpotFilter::All => num_failed > 0 && num_failed == included.len(),
+        };
+        let keep_failed = self.filter_opts.spot_filter == SpotFilter::All && !drop_spot;
+        if drop_spot {
+            stats.inc_filter_spot(self.filter_opts.spot_filter);
+        }
+
+        // Retain the segments passing all filters (under the spot-level policy)
+        included.retain(|selected| match selected.verdict {
+            Some(reason) if !keep_failed => {
+                // Increment filter statistics
+                stats.inc_filter(reason, selected.sid());
+                false
+            }
+            _ => !drop_spot,
+        });
+
+        // Both mates survived the filters
+        let is_paired = included.iter().filter(|s| s.mate.is_some()).count() == 2;
+
+        if self.layout == OutputLayout::Concatenated {
+            return self.write_concatenated(spot_id, &included, local_buffers, counts, stats);
+        }
+        if self.format.is_columnar() {
+            let Some(columns) = columns else {
+                bail!("Columnar output requires a record batch");
+            };
+            return self.write_columnar(spot_id, &included, columns, stats);
+        }
+        if let Some(sam) = &self.sam {
+            return self.write_sam(
+                sam,
+                spot_id,
+                &included,
+                is_paired,
+                &tech,
+                local_buffers,
+                counts,
+                stats,
+            );
+        }
+
+        for selected in included {
+            let output_id = match self.layout {
+                OutputLayout::Interleaved | OutputLayout::Concatenated => 0,
+                OutputLayout::Split => selected.sid(),
+                OutputLayout::Split3 => match selected.mate {
+                    Some(mate) if is_paired => mate,
+                    _ => SPLIT3_UNPAIRED,
+                },
+            };
+
+            // Write the segment to the record set
+            let qual = self.quality.apply(selected.qual());
+            let read = Read {
+                spot_id,
+                sid: selected.sid(),
+                seq: selected.seq(),
+                qual: &qual,
+            };
+            write_read_to_buffer_set(local_buffers, output_id, &read, self.format, &self.defline)?;
+            counts[output_id] += 1;
+
+            // Increment read statistics
+            stats.inc_reads(selected.sid(), selected.seq().len());
+            stats.inc_output(output_id);
+        }
+
+        // Increment record statistics
+        stats.inc_spots();
+        Ok(())
+    }
+
+    /// Joins the passing segments of a spot into a single read and writes it to the local buffers
+    fn write_concatenated(
+        &self,
+        spot_id: u64,
+        included: &[Selected<'_>],
+        local_buffers: &mut [Vec<u8>],
+        counts: &mut [usize],
+        stats: &mut ProcessStatistics,
+    ) -> Result<()> {
+        if !included.is_empty() {
+            let mut seq = Vec::new();
+            let mut qual = Vec::new();
+            for (idx, selected) in included.iter().enumerate() {
+                if let Some(sep) = self.concat_separator.filter(|_| idx > 0) {
+                    seq.push(sep);
+                    qual.push(CONCAT_SEPARATOR_QUAL);
+                }
+                seq.extend_from_slice(selected.seq());
+                qual.extend_from_slice(&self.quality.apply(selected.qual()));
+
+                // Increment read statistics
+                stats.inc_reads(selected.sid(), selected.seq().len());
+            }
+
+            let read = Read {
+                spot_id,
+                sid: 0,
+                seq: &seq,
+                qual: &qual,
+            };
+            write_read_to_buffer_set(local_buffers, 0, &read, self.format, &self.defline)?;
+            counts[0] += 1;
+            stats.inc_output(0);
+        }
+
+        // Increment record statistics
+        stats.inc_spots();
+        Ok(())
+    }
+
+    /// Writes the passing segments of a spot as unaligned SAM/BAM records to the local buffers
+    #[allow(clippy::too_many_arguments)]
+    fn write_sam(
+        &self,
+        sam: &SamEncoder,
+        spot_id: u64,
+        included: &[Selected<'_>],
+        is_paired: bool,
+        tech: &TechTags,
+        local_buffers: &mut [Vec<u8>],
+        counts: &mut [usize],
+        stats: &mut ProcessStatistics,
+    ) -> Result<()> {
+        for selected in included {
+            let qual = self.quality.apply(selected.qual());
+            let read = Read {
+                spot_id,
+                sid: selected.sid(),
+                seq: selected.seq(),
+                qual: &qual,
+            };
+            let flag = unaligned_flag(selected.mate.filter(|_| is_paired));
+            sam.write_record(&mut local_buffers[0], &read, flag, tech)?;
+            counts[0] += 1;
+
+            // Increment read statistics
+            stats.inc_reads(selected.sid(), selected.seq().len());
+            stats.inc_output(0);
+        }
+
+        // Increment record statistics
+        stats.inc_spots();
+        Ok(())
+    }
+
+    /// Appends the passing segments of a spot to the record batch
+    fn write_columnar(
+        &self,
+        spot_id: u64,
+        included: &[Selected<'_>],
+        columns: &mut ColumnarBatch,
+        stats: &mut ProcessStatistics,
+    ) -> Result<()> {
+        for selected in included {
+            let qual = self.quality.apply(selected.qual());
+            let read = Read {
+                spot_id,
+                sid: selected.sid(),
+                seq: selected.seq(),
+                qual: &qual,
+            };
+            columns.append(&read, selected.segment.is_technical())?;
+
+            // Increment read statistics
+            stats.inc_reads(selected.sid(), selected.seq().len());
+            stats.inc_output(0);
+        }
+        columns.end_spot(spot_id);
 
+        // Increment record statistics
+        stats.inc_spots();
+        Ok(())
+    }
+
+    /// Processes all spots of an inclusive range into the local buffers (or record batch)
+    #[allow(clippy::too_many_arguments)]
+    fn process_range(
+        &self,
+        reader: &SraReader,
+        start: u64,
+        stop: u64,
+        local_buffers: &mut [Vec<u8>],
+        counts: &mut [usize],
+        spot_ends: &mut SpotEnds,
+        mut columns: Option<&mut ColumnarBatch>,
+        stats: &mut ProcessStatistics,
+    ) -> Result<()> {
+        for (idx, record) in reader.into_range_iter(start as i64, stop)?.enumerate() {
+            let record = record?;
+            self.process(
+                start + idx as u64,
+                record.into_iter(),
+                local_buffers,
+                counts,
+                columns.as_deref_mut(),
+                stats,
+            )?;
+            spot_ends.push(local_buffers, counts);
+        }
+        Ok(())
+    }
+}
+
+/// Launches worker threads which write batches of up to `batch_size` spots
 fn launch_threads(
     path: &str,
     num_threads: u64,
-    records_per_thread: u64,
-    remainder: u64,
+    blocks: &[SpotRange],
+    batch_size: usize,
     writer: Arc<Mutex<BoxedSegmentWriter>>,
-    filter_opts: FilterOptions,
-    format: OutputFormat,
+    processor: Arc<SpotProcessor>,
+    progress: Arc<Progress>,
 ) -> Result<ProcessStatistics> {
-    // Segments included in the output
-    let segment_set = if filter_opts.include.is_empty() {
-        None
-    } else {
-        // checking a small vector should be faster than a HashSet
-        let set: Vec<usize> = filter_opts.include.clone();
-        Some(set)
-    };
-
+    let queue = Arc::new(SpotQueue::new(blocks, SPOT_CHUNK_SIZE));
     let mut handles = Vec::new();
-    for i in 0..num_threads {
-        let segment_set = segment_set.clone();
-
-        let start = (i * records_per_thread) + 1;
-        let stop = if i == num_threads - 1 {
-            start + records_per_thread + remainder - 1
-        } else {
-            start + records_per_thread - 1
-        };
+    for _ in 0..queue.num_workers(num_threads) {
         let path = path.to_string();
+        let queue = queue.clone();
         let shared_writer = writer.clone();
+        let processor = processor.clone();
+        let progress = progress.clone();
 
         let handle = std::thread::spawn(move || -> Result<ProcessStatistics> {
-            let reader = SraReader::new(&path)?;
-
             // Initialize local buffers and counters
             let mut stats = ProcessStatistics::default();
             let mut local_buffers = shared_writer.lock().generate_local_buffers();
             let mut counts = vec![0; local_buffers.len()];
+            let mut spot_ends = processor.spot_ends();
+            let mut columns = processor.columnar_batch();
+            let mut num_decoded = 0usize;
 
-            // Iterate over record spots and write to buffers
-            for (idx, record) in reader.into_range_iter(start as i64, stop)?.enumerate() {
-                let record = record?;
+            // Spots held in the local buffers
+            let mut buffered = Vec::new();
 
-                // Iterate over segments in the record
-                for segment in record.into_iter() {
-                    // Skip segment if outside of set
-                    if let Some(ref set) = segment_set {
-                        if !set.contains(&segment.sid()) {
-                            continue;
+            // Pull chunks of spots until the queue is drained and write them to buffers
+            let reader = SraReader::new(&path)?;
+            while let Some((_, SpotRange { start, stop })) = queue.next_chunk() {
+                let mut unbuffered = start;
+                for (idx, record) in reader.into_range_iter(start as i64, stop)?.enumerate() {
+                    let record = record?;
+                    let spot_id = start + idx as u64;
+                    processor.process(
+                        spot_id,
+                        record.into_iter(),
+                        &mut local_buffers,
+                        &mut counts,
+                        columns.as_mut(),
+                        &mut stats,
+                    )?;
+                    spot_ends.push(&local_buffers, &counts);
+
+                    // Handle buffer writes at specific intervals
+                    num_decoded += 1;
+                    if num_decoded.is_multiple_of(batch_size) {
+                        buffered.push(SpotRange::new(unbuffered, spot_id));
+                        unbuffered = spot_id + 1;
+                        let batch = finish_batch(columns.as_mut())?;
+                        let num_bytes = buffered_bytes(&local_buffers, batch.as_ref());
+                        let mut writer = shared_writer.lock();
+                        writer.write_spots(&mut local_buffers, &mut counts, &spot_ends)?;
+                        spot_ends.clear();
+                        if let Some(batch) = batch {
+                            writer.write_batch(batch)?;
                         }
+                        writer.mark_spots(&buffered)?;
+                        buffered.clear();
+                        progress.inc(batch_size as u64, num_bytes);
                     }
+                }
+                if unbuffered <= stop {
+                    buffered.push(SpotRange::new(unbuffered, stop));
+                }
+            }
 
-                    // Skip technical segments if required
-                    if filter_opts.skip_technical && segment.is_technical() {
-                        // Increment filter statistics
-                        stats.inc_filter_type(segment.sid());
-                        continue;
-                    }
+            // write remaining buffers
+            let batch = finish_batch(columns.as_mut())?;
+            let num_bytes = buffered_bytes(&local_buffers, batch.as_ref());
+            let mut writer = shared_writer.lock();
+            writer.write_spots(&mut local_buffers, &mut counts, &spot_ends)?;
+            if let Some(batch) = batch {
+                writer.write_batch(batch)?;
+            }
+            writer.mark_spots(&buffered)?;
+            progress.inc((num_decoded % batch_size) as u64, num_bytes);
 
-                    // Skip reads if they are under the minimum read length
-                    if segment.len() < filter_opts.min_read_len {
-                        // Increment filter statistics
-                        stats.inc_filter_size(segment.sid());
-                        continue;
-                    }
+            // Return thread-specific statistics
+            Ok(stats)
+        });
 
-                    // Write the segment to the record set
-                    write_segment_to_buffer_set(&mut local_buffers, &segment, format)?;
+        // Collect all thread handles
+        handles.push(handle);
+    }
 
-                    if counts.len() == 1 {
-                        counts[0] += 1;
-                    } else {
-                        counts[segment.sid()] += 1;
-                    }
+    // Collect all statistics
+    let mut stats = ProcessStatistics::default();
+    for handle in handles {
+        let thread_stats = handle.join().expect("Thread panicked")?;
+        stats = stats + thread_stats;
+    }
 
-                    // Increment read statistics
-                    stats.inc_reads(segment.sid());
-                }
+    // Finalize the outputs
+    writer.lock().finish()?;
 
-                // Handle buffer writes at specific intervals
-                if idx > 0 && idx.is_multiple_of(RECORD_CAPACITY) {
-                    shared_writer
-                        .lock()
-                        .write_all_buffers(&mut local_buffers, &mut counts)?;
-                }
+    Ok(stats)
+}
 
-                // Increment record statistics
-                stats.inc_spots();
-            }
+/// Launches worker threads which pull chunks of up to `chunk_size` spots from a shared queue
+/// and hand them to a reorder stage so that the output follows the archive spot order.
+fn launch_ordered_threads(
+    path: &str,
+    num_threads: u64,
+    blocks: &[SpotRange],
+    chunk_size: u64,
+    writer: BoxedSegmentWriter,
+    processor: Arc<SpotProcessor>,
+    progress: Arc<Progress>,
+) -> Result<ProcessStatistics> {
+    let queue = Arc::new(SpotQueue::new(blocks, chunk_size));
+    let writer = Arc::new(OrderedWriter::new(writer, 2 * num_threads as usize));
 
-            // write remaining buffers
-            shared_writer
-                .lock()
-                .write_all_buffers(&mut local_buffers, &mut counts)?;
+    let mut handles = Vec::new();
+    for _ in 0..queue.num_workers(num_threads) {
+        let path = path.to_string();
+        let ordered_writer = writer.clone();
+        let processor = processor.clone();
+        let queue = queue.clone();
+        let progress = progress.clone();
+
+        let handle = std::thread::spawn(move || -> Result<ProcessStatistics> {
+            let mut stats = ProcessStatistics::default();
+            let reader = SraReader::new(&path)?;
+
+            // Chunks are taken in order so that all threads advance together
+            while let Some((chunk_idx, spots)) = queue.next_chunk() {
+                let SpotRange { start, stop } = spots;
+
+                let mut local_buffers = ordered_writer.generate_local_buffers();
+                let mut counts = vec![0; local_buffers.len()];
+                let mut spot_ends = processor.spot_ends();
+                let mut columns = processor.columnar_batch();
+
+                let batch = processor
+                    .process_range(
+                        &reader,
+                        start,
+                        stop,
+                        &mut local_buffers,
+                        &mut counts,
+                        &mut spot_ends,
+                        columns.as_mut(),
+                        &mut stats,
+                    )
+                    .and_then(|()| finish_batch(columns.as_mut()));
+                let batch = match batch {
+                    Ok(batch) => batch,
+                    Err(err) => {
+                        // Release any threads waiting on this chunk before bailing out
+                        ordered_writer.abort();
+                        return Err(err);
+                    }
+                };
+
+                let num_bytes = buffered_bytes(&local_buffers, batch.as_ref());
+                ordered_writer.submit(chunk_idx, local_buffers, counts, spot_ends, batch, spots)?;
+                progress.inc(spots.len(), num_bytes);
+            }
 
-            // Return thread-specific statistics
             Ok(stats)
         });
 
-        // Collect all thread handles
         handles.push(handle);
     }
 
@@ -129,15 +654,39 @@ fn launch_threads(
         stats = stats + thread_stats;
     }
 
+    // Finalize the outputs
+    writer.finish()?;
+
     Ok(stats)
 }
 
+/// Number of bytes held in the local buffers and the finished record batch
+fn buffered_bytes(local_buffers: &[Vec<u8>], batch: Option<&RecordBatch>) -> u64 {
+    let num_bytes: u64 = local_buffers.iter().map(|buf| buf.len() as u64).sum();
+    num_bytes + batch.map_or(0, |batch| batch.get_array_memory_size() as u64)
+}
+
+/// Finishes the rows appended to a worker's record batch (`None` if there are none)
+fn finish_batch(columns: Option<&mut ColumnarBatch>) -> Result<Option<RecordBatch>> {
+    match columns {
+        Some(columns) if !columns.is_empty() => columns.finish().map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// Dumps the archive into the output files (or streams)
+///
+/// The output files are created within `outputs` and removed unless all spots were written.
 pub fn dump(
     input: &InputOptions,
-    num_threads: u64,
+    runtime: &RuntimeOptions,
     output_opts: &DumpOutput,
-    filter_opts: FilterOptions,
+    mut filter_opts: FilterOptions,
+    quality_opts: &QualityOptions,
+    outputs: PartialOutputs,
 ) -> Result<()> {
+    let start_time = Instant::now();
+    let num_threads = runtime.threads();
     let accession = if !Path::new(&input.accession).exists() {
         eprintln!(
             "Identifying SRA data URL for Accession: {}",
@@ -151,85 +700,268 @@ pub fn dump(
         input.accession.to_string()
     };
 
-    let num_records = get_num_records(&accession)?;
+    // Streams need one sink per output, so split streams only name the segments of the archive
+    if output_opts.is_streamed()
+        && output_opts.layout() == OutputLayout::Split
+        && filter_opts.include.is_empty()
+    {
+        let skip = filter_opts.start.unwrap_or(1) as usize;
+        let num_segments = describe_inner(&accession, skip, 100, 1)?
+            .segment_lengths()
+            .len();
+        filter_opts.include = (0..num_segments).collect();
+    }
+
+    // Validate the output options before creating any outputs
+    let accession_id = accession_name(&input.accession);
+    let defline = Defline::new(
+        output_opts.defline,
+        output_opts.defline_seq.as_deref(),
+        output_opts.defline_qual.as_deref(),
+        &accession_id,
+    )?;
+    let mut processor = SpotProcessor::new(
+        filter_opts,
+        output_opts,
+        quality_opts,
+        defline,
+        &accession_id,
+    )?;
+    let codec = output_opts.codec()?;
+    let filter_opts = &processor.filter_opts;
+
+    // Resolve the spot range to process
+    let range = SpotRange::resolve(
+        get_num_records(&accession)?,
+        filter_opts.start,
+        filter_opts.stop,
+        filter_opts.limit,
+    )?;
+    let spot_list = filter_opts
+        .spot_list
+        .as_deref()
+        .map(|path| SpotList::load(path, range))
+        .transpose()?;
+    processor.sampler = SpotSampler::from_options(
+        filter_opts.sample_frac,
+        filter_opts.sample_count,
+        filter_opts.seed,
+        spot_list
+            .as_ref()
+            .map_or(std::slice::from_ref(&range), SpotList::ranges),
+    )?;
 
-    // Adjust the number of records to process if a limit is provided
-    let num_records = if let Some(limit) = filter_opts.limit {
-        if limit > num_records {
-            eprintln!("Warning: Provided spot limit ({limit}) is greater than the actual number of spots ({num_records}). Will process the full archive.");
+    // Skip the spots completed by a previous run
+    let checkpoint_path = output_opts.checkpoint_path();
+    let settings = output_settings(output_opts, filter_opts, quality_opts);
+    let checkpoint = if output_opts.resume {
+        Some(Checkpoint::resume(
+            &checkpoint_path,
+            &accession_id,
+            settings,
+        )?)
+    } else if output_opts.checkpoint {
+        Some(Checkpoint::new(&accession_id, settings))
+    } else {
+        None
+    };
+    processor.spot_list = match &checkpoint {
+        Some(checkpoint) => {
+            let selected = spot_list.unwrap_or_else(|| SpotList::from_ranges(vec![range]));
+            let remaining = selected.difference(&checkpoint.completed());
+            if remaining.is_empty() {
+                eprintln!("All selected spots were already written (see {checkpoint_path})");
+            }
+            Some(remaining)
         }
-        num_records.min(limit)
+        None => spot_list,
+    };
+    let blocks = match &processor.spot_list {
+        Some(list) => list.ranges().to_vec(),
+        None => vec![range],
+    };
+
+    if output_opts.manifest.is_some() {
+        outputs.summarize();
+    }
+
+    let layout = output_opts.layout();
+    let mut writer: BoxedSegmentWriter = if let Some(checkpoint) = checkpoint {
+        std::fs::create_dir_all(&output_opts.outdir)?;
+        let paths = layout
+            .output_names(&filter_opts.include)
+            .into_iter()
+            .map(|name| {
+                name.map(|name| {
+                    outputs.create_resumable(&build_path_name(
+                        OutputFileType::RegularFile(&output_opts.outdir),
+                        &output_opts.prefix,
+                        codec.compression,
+                        output_opts.format,
+                        name,
+                    ))
+                })
+                .transpose()
+            })
+            .collect::<Result<_>>()?;
+        Box::new(CheckpointWriter::new(
+            paths,
+            codec,
+            num_threads as usize,
+            checkpoint,
+            &checkpoint_path,
+        )?)
+    } else if output_opts.format.is_columnar() {
+        build_columnar_writer(
+            &output_opts.outdir,
+            &accession_id,
+            output_opts.format,
+            output_opts.rows,
+            codec,
+            filter_opts,
+            &outputs,
+        )?
+    } else {
+        build_segment_writer(
+            &output_opts.outdir,
+            &output_opts.prefix,
+            codec,
+            output_opts.format,
+            num_threads as usize,
+            filter_opts,
+            output_opts.named_pipes,
+            &output_opts.sinks(),
+            layout,
+            output_opts.chunk_limits(),
+            &outputs,
+        )?
+    };
+
+    // Write the SAM/BAM header ahead of any records
+    if let Some(sam) = &processor.sam {
+        writer.write_all_buffers(&mut [sam.header()], &mut [1])?;
+    }
+
+    // Chunked outputs only create parts on their first write, so none can be empty,
+    // resumed outputs may hold the reads of a previous run, and streams are not files
+    let output_names = if output_opts.chunk_limits().is_some()
+        || output_opts.resume
+        || output_opts.is_streamed()
+    {
+        Vec::new()
     } else {
-        num_records
+        layout.output_names(&filter_opts.include)
     };
+    let processor = Arc::new(processor);
+
+    // Report the decoded spots across all worker threads
+    let progress = Arc::new(Progress::new(blocks.iter().map(SpotRange::len).sum())?);
+    let logger = runtime
+        .progress_interval()
+        .map(|interval| ProgressLogger::spawn(progress.clone(), interval));
 
-    // Calculate records per thread and remainder (final thread)
-    let records_per_thread = num_records / num_threads;
-    let remainder = num_records % num_threads;
-
-    let writer = build_segment_writer(
-        Some(&output_opts.outdir),
-        &output_opts.prefix,
-        output_opts.compression,
-        output_opts.format,
-        num_threads as usize,
-        &filter_opts,
-        output_opts.named_pipes,
-        output_opts.split,
-    )
-    .map(|x| Arc::new(Mutex::new(x)))?;
-
-    let included_segs = filter_opts.include.clone();
     // Launch worker threads
-    let stats = launch_threads(
-        &accession,
-        num_threads,
-        records_per_thread,
-        remainder,
-        writer,
-        filter_opts,
-        output_opts.format,
-    )?;
+    let stats = if output_opts.ordered {
+        launch_ordered_threads(
+            &accession,
+            num_threads,
+            &blocks,
+            SPOT_CHUNK_SIZE,
+            writer,
+            processor,
+            progress.clone(),
+        )?
+    } else {
+        launch_threads(
+            &accession,
+            num_threads,
+            &blocks,
+            RECORD_CAPACITY,
+            Arc::new(Mutex::new(writer)),
+            processor,
+            progress.clone(),
+        )?
+    };
+    progress.finish();
+    drop(logger);
+
+    // Rename the complete outputs to their final names
+    let summaries = outputs.commit()?;
 
     // Remove empty files
-    if output_opts.split {
-        let wrap = |x| {
-            if output_opts.named_pipes {
-                OutputFileType::NamedPipe(x)
+    for (output_id, name) in output_names.iter().enumerate() {
+        // segments which were not included never had a real file created
+        let Some(name) = name else {
+            continue;
+        };
+        let count = stats.reads_per_output.get(output_id).copied().unwrap_or(0);
+        if count == 0 || output_opts.named_pipes {
+            // same codec as the writers, as BAM outputs are always bgzipped
+            let path = build_path_name(
+                OutputFileType::file(&output_opts.outdir, output_opts.named_pipes),
+                &output_opts.prefix,
+                codec.compression,
+                output_opts.format,
+                name,
+            );
+            if output_opts.keep_empty {
+                eprintln!("Warning => empty path: {path}");
             } else {
-                OutputFileType::RegularFile(x)
+                eprintln!("Removing empty path: {path}");
+                std::fs::remove_file(&path)?;
+                index::remove(&path)?;
             }
-        };
-        stats.reads_per_segment.iter().enumerate().try_for_each(
-            |(seg_id, &count)| -> Result<()> {
-                // if included_segs was non-empty, so we are applying a filter
-                // and this segment was not included, then we didn't create a real
-                // file for it.
-                if !included_segs.is_empty() && !included_segs.contains(&seg_id) {
-                    return Ok(());
-                }
-                if count == 0 || output_opts.named_pipes {
-                    let path = build_path_name(
-                        wrap(&output_opts.outdir),
-                        &output_opts.prefix,
-                        output_opts.compression,
-                        output_opts.format,
-                        seg_id,
-                    );
-                    if output_opts.keep_empty {
-                        eprintln!("Warning => empty path: {path}");
-                    } else {
-                        eprintln!("Removing empty path: {path}");
-                        std::fs::remove_file(path)?;
-                    }
-                }
-                Ok(())
-            },
-        )?;
+        }
     }
 
-    // Print all statistics
-    stats.pprint(&mut std::io::stderr())?;
+    // Record the provenance of the remaining outputs
+    if let Some(path) = &output_opts.manifest {
+        // parquet files are compressed by their column codec rather than as a whole
+        if output_opts.format == OutputFormat::Parquet {
+            let codec_name = parquet_codec_name(codec)?;
+            for summary in &summaries {
+                summary.set_compression(codec_name);
+            }
+        }
+        write_manifest(path, &summaries, &accession_id, &accession, &stats)?;
+    }
+
+    // Report all statistics
+    let report = StatsReport::new(&stats, start_time.elapsed());
+    match &output_opts.stats_file {
+        Some(path) => {
+            let mut wtr = BufWriter::new(File::create(path)?);
+            report.write(&mut wtr, output_opts.stats_format)?;
+            wtr.flush()?;
+        }
+        None => report.write(&mut std::io::stderr(), output_opts.stats_format)?,
+    }
 
     Ok(())
 }
+
+/// Writes the manifest of the summarized output files which were not removed
+fn write_manifest(
+    path: &str,
+    summaries: &[OutputSummary],
+    accession: &str,
+    source: &str,
+    stats: &ProcessStatistics,
+) -> Result<()> {
+    let mut entries: Vec<_> = summaries
+        .iter()
+        .filter(|summary| Path::new(summary.path()).exists())
+        .map(OutputSummary::entry)
+        .collect();
+    // Records of other formats are not counted while writing, but a single
+    // output file (e.g. parquet or interleaved BAM) holds all written reads
+    if let [entry] = entries.as_mut_slice() {
+        if entry.reads.is_none() {
+            entry.reads = Some(stats.num_reads);
+            entry.bases = Some(stats.num_bases);
+        }
+    }
+    Manifest::new(accession, source, entries).save(path)?;
+    eprintln!("Wrote manifest of the outputs to {path}");
+    Ok(())
+}
@@ -1,31 +1,30 @@
+use std::collections::BTreeMap;
 use std::io::Write;
 use std::sync::mpsc;
-use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use arrow::record_batch::RecordBatch;
 use parking_lot::Condvar;
 use parking_lot::Mutex;
 
 use crate::{
-    cli::{FilterOptions, OutputFormat},
-    output::{build_writers, Compression},
+    cli::{FilterOptions, OutputFormat, OutputLayout},
+    output::{
+        build_named_writers, build_part_writer, build_sink_writers, build_writers, BoxedWriter,
+        ChunkLimits, Codec, OutputFileType,
+    },
+    partial::PartialOutputs,
+    spots::SpotRange,
     BUFFER_SIZE,
 };
 
 /// Set the default buffer size to 1MB
 const DEFAULT_BUFFER_SIZE: usize = 1024 * 1024;
 
-/// Set the maximum overflow buffer size to 128MB
+/// Set the memory queued for each writer thread to 128MB (in buffers of the default size)
 const MAXIMUM_BUFFER_SIZE: usize = 128 * 1024 * 1024;
 
-/// Sets the default sleep time (in milliseconds)
-const DEFAULT_SLEEP_MS: u64 = 100;
-
-/// A shorthand for the type of output handles we expect to write
-pub type BoxedWriter = Box<dyn Write + Send>;
-
 /// A shorthand for the type of writers we expect to use
 pub type BoxedSegmentWriter = Box<dyn SegmentWriter + Send>;
 
@@ -37,151 +36,355 @@ pub trait SegmentWriter {
     /// Write all the segments to their respective IO handles
     fn write_all_buffers(&mut self, buffers: &mut [Vec<u8>], counts: &mut [usize]) -> Result<()>;
 
+    /// Write all the segments, given where each spot of the buffers ends
+    ///
+    /// Only chunked outputs make use of the spot ends, to start a new part within the buffers.
+    fn write_spots(
+        &mut self,
+        buffers: &mut [Vec<u8>],
+        counts: &mut [usize],
+        _ends: &SpotEnds,
+    ) -> Result<()> {
+        self.write_all_buffers(buffers, counts)
+    }
+
+    /// Write a record batch which was built on a worker thread (columnar outputs only)
+    fn write_batch(&mut self, _batch: RecordBatch) -> Result<()> {
+        bail!("Record batches can only be written to columnar outputs")
+    }
+
+    /// Record that all spots of the ranges were written with the preceding buffers
+    fn mark_spots(&mut self, _spots: &[SpotRange]) -> Result<()> {
+        Ok(())
+    }
+
+    /// Finalize the outputs once all buffers have been written
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+
     /// Return local buffers to mimic the expected writer buffers on-thread
     fn generate_local_buffers(&self) -> Vec<Vec<u8>> {
         vec![Vec::with_capacity(BUFFER_SIZE); self.num_segments()]
     }
 }
 
+/// Lengths and read counts of a set of local buffers at the end of each spot
+///
+/// Only recorded for chunked outputs, so that a part can end at any spot of a buffer set.
+#[derive(Debug, Clone, Default)]
+pub struct SpotEnds {
+    enabled: bool,
+    /// Length and read count of every buffer after each spot
+    ends: Vec<(usize, usize)>,
+}
+impl SpotEnds {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ends: Vec::new(),
+        }
+    }
+
+    /// Records the end of a spot (if enabled)
+    pub fn push(&mut self, buffers: &[Vec<u8>], counts: &[usize]) {
+        if self.enabled {
+            self.ends.extend(
+                buffers
+                    .iter()
+                    .zip(counts)
+                    .map(|(buf, &count)| (buf.len(), count)),
+            );
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.ends.clear();
+    }
+
+    /// Ends of the recorded spots (one entry per buffer)
+    fn spots(&self, num_buffers: usize) -> impl Iterator<Item = &[(usize, usize)]> {
+        self.ends.chunks_exact(num_buffers)
+    }
+}
+
 /// Handles the creation logic and pipes the IO to the right Writer struct.
 #[allow(clippy::too_many_arguments)]
 pub fn build_segment_writer(
-    outdir: Option<&str>,
+    outdir: &str,
     prefix: &str,
-    compression: Compression,
+    codec: Codec,
     format: OutputFormat,
     num_threads: usize,
     filter_opts: &FilterOptions,
     is_fifo: bool,
-    is_split: bool,
+    sinks: &[OutputFileType],
+    layout: OutputLayout,
+    chunks: Option<ChunkLimits>,
+    outputs: &PartialOutputs,
 ) -> Result<BoxedSegmentWriter> {
-    if is_split {
-        if is_fifo {
-            let wtr = BufferedWriter::new(
-                outdir,
-                prefix,
-                compression,
-                format,
-                num_threads,
-                filter_opts,
-                is_fifo,
-            )?;
-            Ok(Box::new(wtr))
+    if let Some(limits) = chunks {
+        return Ok(Box::new(RotatingWriter::new(
+            outdir,
+            prefix,
+            codec,
+            format,
+            num_threads,
+            layout.output_names(&filter_opts.include),
+            limits,
+            outputs.clone(),
+        )));
+    }
+    if !sinks.is_empty() {
+        let handles = build_sink_writers(
+            sinks,
+            &layout.output_names(&filter_opts.include),
+            codec,
+            format,
+            num_threads,
+            outputs,
+        )?;
+        // streams block on slow readers just like named pipes
+        return if layout.is_split() {
+            Ok(Box::new(BufferedWriter::new(handles)))
         } else {
-            let wtr = DirectWriter::new(
-                outdir,
-                prefix,
-                compression,
-                format,
-                num_threads,
-                filter_opts,
-                is_fifo,
-            )?;
-            Ok(Box::new(wtr))
-        }
-    } else {
-        let wtr = DirectWriter::new(
+            Ok(Box::new(DirectWriter::new(handles)))
+        };
+    }
+    let handles = match layout {
+        OutputLayout::Interleaved | OutputLayout::Concatenated => build_writers(
             None,
             prefix,
-            compression,
+            codec,
             format,
             num_threads,
             filter_opts,
             false,
-        )?;
-        Ok(Box::new(wtr))
+            outputs,
+        )?,
+        OutputLayout::Split => build_writers(
+            Some(outdir),
+            prefix,
+            codec,
+            format,
+            num_threads,
+            filter_opts,
+            is_fifo,
+            outputs,
+        )?,
+        OutputLayout::Split3 => build_named_writers(
+            outdir,
+            prefix,
+            codec,
+            format,
+            num_threads,
+            &layout.output_names(&filter_opts.include),
+            is_fifo,
+            outputs,
+        )?,
+    };
+    if is_fifo && layout.is_split() {
+        Ok(Box::new(BufferedWriter::new(handles)))
+    } else {
+        Ok(Box::new(DirectWriter::new(handles)))
     }
 }
 
-/// A thead-local writer that owns a subprocess handling the actual writing
-struct ThreadWriter {
-    /// Owned reusable write buffer with a conditional variable marking when it's been written to
-    buffer_pair: Arc<(Mutex<Vec<u8>>, Condvar)>,
-    /// The signal to end the subprocess
-    shutdown_sender: mpsc::Sender<()>,
-    /// Handle to the owned subprocess
-    join_handle: Option<thread::JoinHandle<Result<()>>>,
+/// A chunk of filled buffers (or a record batch) waiting for its turn to be written
+struct PendingChunk {
+    buffers: Vec<Vec<u8>>,
+    counts: Vec<usize>,
+    ends: SpotEnds,
+    batch: Option<RecordBatch>,
+    spots: SpotRange,
 }
 
-impl ThreadWriter {
-    fn new(mut handle: BoxedWriter) -> Self {
-        let buffer_pair = Arc::new((Mutex::new(Vec::new()), Condvar::new()));
-        let buffer_pair_clone = Arc::clone(&buffer_pair);
+/// Shared state of the reorder stage
+struct ReorderState {
+    /// The downstream writer receiving chunks in order
+    inner: BoxedSegmentWriter,
+    /// Index of the next chunk to be written
+    next: usize,
+    /// Chunks which were completed ahead of their turn
+    pending: BTreeMap<usize, PendingChunk>,
+    /// Set when any thread failed so that waiting threads can bail out
+    aborted: bool,
+}
 
-        let (shutdown_sender, shutdown_receiver) = mpsc::channel();
+/// A bounded reorder stage in front of a [`SegmentWriter`].
+///
+/// Worker threads submit indexed chunks of spots which are written downstream strictly in
+/// index order. Threads submitting chunks too far ahead of the next expected chunk are blocked
+/// until the window advances, which bounds the number of chunks held in memory.
+pub struct OrderedWriter {
+    state: Mutex<ReorderState>,
+    cvar: Condvar,
+    /// Maximum distance between a submitted chunk and the next chunk to be written
+    window: usize,
+}
+impl OrderedWriter {
+    pub fn new(inner: BoxedSegmentWriter, window: usize) -> Self {
+        Self {
+            state: Mutex::new(ReorderState {
+                inner,
+                next: 0,
+                pending: BTreeMap::new(),
+                aborted: false,
+            }),
+            cvar: Condvar::new(),
+            window: window.max(1),
+        }
+    }
 
-        // Start the worker thread
-        let join_handle = thread::spawn(move || -> Result<()> {
-            let (buffer, cvar) = &*buffer_pair_clone;
+    /// Return local buffers to mimic the expected writer buffers on-thread
+    pub fn generate_local_buffers(&self) -> Vec<Vec<u8>> {
+        self.state.lock().inner.generate_local_buffers()
+    }
+
+    /// Submit a completed chunk of spots and write out all chunks which are now in order
+    pub fn submit(
+        &self,
+        idx: usize,
+        buffers: Vec<Vec<u8>>,
+        counts: Vec<usize>,
+        ends: SpotEnds,
+        batch: Option<RecordBatch>,
+        spots: SpotRange,
+    ) -> Result<()> {
+        let mut state = self.state.lock();
+        while idx >= state.next + self.window && !state.aborted {
+            self.cvar.wait(&mut state);
+        }
+        if state.aborted {
+            bail!("Ordered output was aborted by another worker thread");
+        }
+        let chunk = PendingChunk {
+            buffers,
+            counts,
+            ends,
+            batch,
+            spots,
+        };
+        state.pending.insert(idx, chunk);
+
+        // Drain all chunks that are ready to be written
+        loop {
+            let next = state.next;
+            let Some(mut chunk) = state.pending.remove(&next) else {
+                break;
+            };
+            let written = state
+                .inner
+                .write_spots(&mut chunk.buffers, &mut chunk.counts, &chunk.ends)
+                .and_then(|_| match chunk.batch {
+                    Some(batch) => state.inner.write_batch(batch),
+                    None => Ok(()),
+                })
+                .and_then(|_| state.inner.mark_spots(&[chunk.spots]));
+            if let Err(err) = written {
+                state.aborted = true;
+                self.cvar.notify_all();
+                return Err(err);
+            }
+            state.next += 1;
+        }
+        self.cvar.notify_all();
+        Ok(())
+    }
 
-            loop {
-                // Wait for data or shutdown signal
-                let mut guard = buffer.lock();
+    /// Finalize the inner writer once all chunks have been submitted
+    pub fn finish(&self) -> Result<()> {
+        self.state.lock().inner.finish()
+    }
 
-                while guard.is_empty() {
-                    // Check for shutdown before waiting
-                    if shutdown_receiver.try_recv().is_ok() {
-                        return Ok(()); // Exit the thread
-                    }
+    /// Mark the stage as failed and wake all waiting threads
+    pub fn abort(&self) {
+        self.state.lock().aborted = true;
+        self.cvar.notify_all();
+    }
+}
 
-                    // Wait for notification that data is available
-                    cvar.wait(&mut guard);
+/// Maximum number of filled buffers queued for each writer thread
+const QUEUED_BUFFERS: usize = MAXIMUM_BUFFER_SIZE / DEFAULT_BUFFER_SIZE;
 
-                    // Check again for shutdown after waking
-                    if shutdown_receiver.try_recv().is_ok() {
-                        return Ok(()); // Exit the thread
-                    }
-                }
+/// A writer that owns a thread handling the actual writing
+///
+/// Filled buffers are passed to the thread over a bounded channel, so ingesting blocks
+/// once the thread falls behind. Written buffers are sent back to be reused.
+struct ThreadWriter {
+    /// Filled buffers queued for the writer thread (dropped to end the thread)
+    sender: Option<mpsc::SyncSender<Vec<u8>>>,
+    /// Buffers which were written and can be refilled
+    recycled: mpsc::Receiver<Vec<u8>>,
+    /// Handle to the owned thread
+    join_handle: Option<thread::JoinHandle<Result<()>>>,
+}
 
-                // We have data to process
-                let mut data = std::mem::take(&mut *guard);
-                drop(guard); // Release lock before I/O
+impl ThreadWriter {
+    fn new(mut handle: BoxedWriter) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<Vec<u8>>(QUEUED_BUFFERS);
+        let (recycler, recycled) = mpsc::channel();
 
+        // Start the worker thread which writes until all senders hung up
+        let join_handle = thread::spawn(move || -> Result<()> {
+            for mut buffer in receiver {
                 // Perform actual write (potentially blocking I/O)
-                handle.write_all(data.drain(..).as_slice())?;
+                handle.write_all(&buffer)?;
                 handle.flush()?;
+
+                // The ingesting side may already be gone, in which case the buffer is dropped
+                buffer.clear();
+                let _ = recycler.send(buffer);
             }
+            handle.finish()
         });
 
         ThreadWriter {
-            buffer_pair,
-            shutdown_sender,
+            sender: Some(sender),
+            recycled,
             join_handle: Some(join_handle),
         }
     }
 
-    fn ingest(&self, data: &[u8]) {
-        let (buffer, cvar) = &*self.buffer_pair;
-        loop {
-            let mut guard = buffer.lock();
-            if guard.len() <= MAXIMUM_BUFFER_SIZE {
-                guard.extend_from_slice(data);
-                cvar.notify_one();
-                break;
-            } else {
-                thread::sleep(Duration::from_millis(DEFAULT_SLEEP_MS));
-            }
+    /// Queues the filled buffer for writing and replaces it with an empty one
+    ///
+    /// Blocks while the queue is full, i.e. until the writer thread caught up.
+    fn ingest(&mut self, buffer: &mut Vec<u8>) -> Result<()> {
+        let Some(sender) = &self.sender else {
+            bail!("Cannot write to an output which was already finished");
+        };
+        let empty = self
+            .recycled
+            .try_recv()
+            .unwrap_or_else(|_| Vec::with_capacity(DEFAULT_BUFFER_SIZE));
+        if sender.send(std::mem::replace(buffer, empty)).is_err() {
+            // The writer thread only hangs up early if writing failed
+            self.finish()?;
+            bail!("Writer thread exited before all buffers were written");
+        }
+        Ok(())
+    }
+
+    /// Waits until all queued buffers were written and the handle was finished
+    ///
+    /// Reports any error of the writer thread.
+    fn finish(&mut self) -> Result<()> {
+        self.sender.take();
+        match self.join_handle.take() {
+            Some(handle) => match handle.join() {
+                Ok(result) => result,
+                Err(_) => bail!("Writer thread panicked"),
+            },
+            None => Ok(()),
         }
     }
 }
 
 impl Drop for ThreadWriter {
     fn drop(&mut self) {
-        // Signal thread to shut down
-        self.shutdown_sender
-            .send(())
-            .expect("Error in sending signal");
-
-        // notify the condition variable to wake up the worker thread
-        let (_buffer, cvar) = &*self.buffer_pair;
-        cvar.notify_all(); // make sure to wake the thread even if the buffer is empty
-
-        // Wait for thread to finish
-        if let Some(handle) = self.join_handle.take() {
-            handle
-                .join()
-                .expect("Error in joining thread")
-                .expect("Error within thread");
+        // Errors are only reported here if the writer was not finished beforehand
+        if let Err(err) = self.finish() {
+            eprintln!("Error in writing output: {err}");
         }
     }
 }
@@ -194,40 +397,23 @@ pub struct BufferedWriter {
     thread_writers: Vec<ThreadWriter>,
 }
 impl BufferedWriter {
-    pub fn new(
-        outdir: Option<&str>,
-        prefix: &str,
-        compression: Compression,
-        format: OutputFormat,
-        num_threads: usize,
-        filter_opts: &FilterOptions,
-        is_fifo: bool,
-    ) -> Result<Self> {
-        let segment_handles = build_writers(
-            outdir,
-            prefix,
-            compression,
-            format,
-            num_threads,
-            filter_opts,
-            is_fifo,
-        )?;
+    pub fn new(segment_handles: Vec<BoxedWriter>) -> Self {
         let segment_buffers = vec![Vec::with_capacity(DEFAULT_BUFFER_SIZE); segment_handles.len()];
         let thread_writers = segment_handles.into_iter().map(ThreadWriter::new).collect();
-        Ok(Self {
+        Self {
             segment_buffers,
             thread_writers,
-        })
+        }
     }
 
     fn write_to_handles(&mut self) -> Result<()> {
         for (writer, buf) in self
             .thread_writers
-            .iter()
+            .iter_mut()
             .zip(self.segment_buffers.iter_mut())
         {
             if !buf.is_empty() {
-                writer.ingest(buf.drain(..).as_slice());
+                writer.ingest(buf)?;
             }
         }
         Ok(())
@@ -248,40 +434,31 @@ impl SegmentWriter for BufferedWriter {
             if *local_count == 0 {
                 continue;
             }
-            shared_buf.extend_from_slice(local_buf);
-            local_buf.clear();
+            // the shared buffer was emptied by the last ingest
+            std::mem::swap(shared_buf, local_buf);
             *local_count = 0;
         }
 
         self.write_to_handles()
     }
+
+    fn finish(&mut self) -> Result<()> {
+        for writer in self.thread_writers.iter_mut() {
+            writer.finish()?;
+        }
+        Ok(())
+    }
 }
 
 /// A Writer struct which writes directly to output handles without any buffering
 pub struct DirectWriter {
+    /// Taken on finish
     segment_handles: Vec<BoxedWriter>,
 }
 
 impl DirectWriter {
-    pub fn new(
-        outdir: Option<&str>,
-        prefix: &str,
-        compression: Compression,
-        format: OutputFormat,
-        num_threads: usize,
-        filter_opts: &FilterOptions,
-        is_fifo: bool,
-    ) -> Result<Self> {
-        let segment_handles = build_writers(
-            outdir,
-            prefix,
-            compression,
-            format,
-            num_threads,
-            filter_opts,
-            is_fifo,
-        )?;
-        Ok(Self { segment_handles })
+    pub fn new(segment_handles: Vec<BoxedWriter>) -> Self {
+        Self { segment_handles }
     }
 }
 
@@ -306,11 +483,190 @@ impl SegmentWriter for DirectWriter {
         }
         Ok(())
     }
+
+    fn finish(&mut self) -> Result<()> {
+        for handle in self.segment_handles.drain(..) {
+            handle.finish()?;
+        }
+        Ok(())
+    }
+}
+
+/// A writer struct which splits each output file into numbered parts.
+///
+/// Parts are rotated at the spot ends recorded with each buffer set (or between buffer sets,
+/// which always hold complete spots), so all output files (i.e. mates) start a new part at
+/// the same spot boundary.
+/// Part files are created on their first write so that no empty parts are left behind.
+pub struct RotatingWriter {
+    outdir: String,
+    prefix: String,
+    codec: Codec,
+    format: OutputFormat,
+    num_threads: usize,
+    /// Output names in handle order (`None` for excluded segments)
+    names: Vec<Option<String>>,
+    limits: ChunkLimits,
+    /// Index of the current part (1-indexed, 0 before the first write)
+    part: usize,
+    /// Handles of the current part
+    handles: Vec<Option<BoxedWriter>>,
+    /// Reads written to each file of the current part
+    part_reads: Vec<u64>,
+    /// Bytes written to each file of the current part
+    part_bytes: Vec<u64>,
+    /// The outputs of the run which the parts are created in
+    outputs: PartialOutputs,
+}
+impl RotatingWriter {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        outdir: &str,
+        prefix: &str,
+        codec: Codec,
+        format: OutputFormat,
+        num_threads: usize,
+        names: Vec<Option<String>>,
+        limits: ChunkLimits,
+        outputs: PartialOutputs,
+    ) -> Self {
+        let num_outputs = names.len();
+        Self {
+            outdir: outdir.to_string(),
+            prefix: prefix.to_string(),
+            codec,
+            format,
+            num_threads,
+            names,
+            limits,
+            part: 0,
+            handles: (0..num_outputs).map(|_| None).collect(),
+            part_reads: vec![0; num_outputs],
+            part_bytes: vec![0; num_outputs],
+            outputs,
+        }
+    }
+
+    /// Close the handles of the current part and move on to the next one
+    fn rotate(&mut self) -> Result<()> {
+        self.close()?;
+        self.part += 1;
+        self.part_reads.fill(0);
+        self.part_bytes.fill(0);
+        Ok(())
+    }
+
+    /// Writes the spots between two ends of the buffers to the current part
+    fn write_range(
+        &mut self,
+        buffers: &[Vec<u8>],
+        from: &[(usize, usize)],
+        to: &[(usize, usize)],
+    ) -> Result<()> {
+        for (idx, buf) in buffers.iter().enumerate() {
+            let num_reads = to[idx].1 - from[idx].1;
+            // Skip writing empty segments
+            if num_reads == 0 {
+                continue;
+            }
+            let Some(name) = &self.names[idx] else {
+                bail!("Received reads for an output without a file (handle {idx})");
+            };
+            let handle = match &mut self.handles[idx] {
+                Some(handle) => handle,
+                slot => slot.insert(build_part_writer(
+                    &self.outdir,
+                    &self.prefix,
+                    self.codec,
+                    self.format,
+                    self.num_threads,
+                    name,
+                    self.part,
+                    &self.outputs,
+                )?),
+            };
+            handle.write_all(&buf[from[idx].0..to[idx].0])?;
+            handle.flush()?;
+            self.part_reads[idx] += num_reads as u64;
+            self.part_bytes[idx] += (to[idx].0 - from[idx].0) as u64;
+        }
+        Ok(())
+    }
+
+    /// Finish all open handles (which finalizes their compression streams)
+    fn close(&mut self) -> Result<()> {
+        for handle in self.handles.iter_mut() {
+            if let Some(handle) = handle.take() {
+                handle.finish()?;
+            }
+        }
+        Ok(())
+    }
+}
+impl SegmentWriter for RotatingWriter {
+    fn num_segments(&self) -> usize {
+        self.names.len()
+    }
+
+    fn write_all_buffers(&mut self, buffers: &mut [Vec<u8>], counts: &mut [usize]) -> Result<()> {
+        // Without recorded spot ends the buffers are treated as a single spot
+        self.write_spots(buffers, counts, &SpotEnds::default())
+    }
+
+    fn write_spots(
+        &mut self,
+        buffers: &mut [Vec<u8>],
+        counts: &mut [usize],
+        ends: &SpotEnds,
+    ) -> Result<()> {
+        if self.part == 0 {
+            self.rotate()?;
+        }
+
+        let empty = vec![(0, 0); buffers.len()];
+        let full: Vec<_> = buffers
+            .iter()
+            .zip(counts.iter())
+            .map(|(buf, &count)| (buf.len(), count))
+            .collect();
+
+        // Start a new part before any spot which would make a file cross a threshold
+        let mut start = empty.as_slice();
+        let mut prev = empty.as_slice();
+        for end in ends.spots(buffers.len()).chain([full.as_slice()]) {
+            let exceeded = (0..self.names.len()).any(|idx| {
+                end[idx].1 > prev[idx].1
+                    && self.limits.exceeded_by(
+                        self.part_reads[idx] + (prev[idx].1 - start[idx].1) as u64,
+                        self.part_bytes[idx] + (prev[idx].0 - start[idx].0) as u64,
+                        (end[idx].1 - prev[idx].1) as u64,
+                        (end[idx].0 - prev[idx].0) as u64,
+                    )
+            });
+            if exceeded {
+                self.write_range(buffers, start, prev)?;
+                self.rotate()?;
+                start = prev;
+            }
+            prev = end;
+        }
+        self.write_range(buffers, start, prev)?;
+
+        buffers.iter_mut().for_each(Vec::clear);
+        counts.fill(0);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.close()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::output::{Compression, OutputStream};
+    use crate::partial::PartialOutputs;
     use std::io::{self, Write};
     use std::sync::{Arc, Mutex};
 
@@ -329,6 +685,11 @@ mod tests {
             Ok(())
         }
     }
+    impl OutputStream for TestWriter {
+        fn finish(self: Box<Self>) -> anyhow::Result<()> {
+            Ok(())
+        }
+    }
 
     // DirectWriter::write_all_buffers tests
     #[test]
@@ -337,10 +698,10 @@ mod tests {
         let data1 = Arc::new(Mutex::new(Vec::new()));
         let data2 = Arc::new(Mutex::new(Vec::new()));
 
-        let writer1: Box<dyn Write + Send> = Box::new(TestWriter {
+        let writer1: BoxedWriter = Box::new(TestWriter {
             data: data1.clone(),
         });
-        let writer2: Box<dyn Write + Send> = Box::new(TestWriter {
+        let writer2: BoxedWriter = Box::new(TestWriter {
             data: data2.clone(),
         });
 
@@ -365,4 +726,223 @@ mod tests {
         assert_eq!(written1, b"ACGT");
         assert!(written2.is_empty());
     }
+
+    // BufferedWriter tests
+    #[test]
+    fn buffered_writer_writes_all_buffers_in_order() {
+        let data = Arc::new(Mutex::new(Vec::new()));
+        let handles: Vec<BoxedWriter> = vec![Box::new(TestWriter { data: data.clone() })];
+        let mut writer = BufferedWriter::new(handles);
+
+        let mut expected = Vec::new();
+        for i in 0..1000 {
+            let record = format!("@{i}\nACGT\n+\nIIII\n").into_bytes();
+            expected.extend_from_slice(&record);
+            writer.write_all_buffers(&mut [record], &mut [1]).unwrap();
+        }
+        writer.finish().unwrap();
+
+        assert_eq!(*data.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn buffered_writer_reports_write_errors() {
+        struct FailingWriter;
+        impl Write for FailingWriter {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "reader went away",
+                ))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        impl OutputStream for FailingWriter {
+            fn finish(self: Box<Self>) -> anyhow::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut writer = BufferedWriter::new(vec![Box::new(FailingWriter)]);
+        // the error surfaces on a later write or once the writer is finished (never as a panic)
+        let result = (0..1000)
+            .try_for_each(|_| writer.write_all_buffers(&mut [b"@1\nA\n+\nI\n".to_vec()], &mut [1]))
+            .and_then(|_| writer.finish());
+        assert!(result.unwrap_err().to_string().contains("reader went away"));
+    }
+
+    // ThreadWriter::ingest tests
+    #[test]
+    fn thread_writer_recycles_written_buffers() {
+        let data = Arc::new(Mutex::new(Vec::new()));
+        let mut writer = ThreadWriter::new(Box::new(TestWriter { data: data.clone() }));
+
+        let mut buffer = Vec::with_capacity(4 * DEFAULT_BUFFER_SIZE);
+        buffer.extend_from_slice(b"A");
+        writer.ingest(&mut buffer).unwrap();
+        assert!(buffer.is_empty());
+
+        // the written buffer is sent back with its allocation
+        let recycled = writer.recycled.recv().unwrap();
+        assert!(recycled.is_empty());
+        assert!(recycled.capacity() >= 4 * DEFAULT_BUFFER_SIZE);
+
+        writer.finish().unwrap();
+        assert_eq!(data.lock().unwrap().as_slice(), b"A");
+    }
+
+    // OrderedWriter::submit tests
+    #[test]
+    fn ordered_writer_emits_chunks_in_index_order() {
+        let data = Arc::new(Mutex::new(Vec::new()));
+        let writer: BoxedWriter = Box::new(TestWriter { data: data.clone() });
+        let inner: BoxedSegmentWriter = Box::new(DirectWriter {
+            segment_handles: vec![writer],
+        });
+        let ordered = OrderedWriter::new(inner, 4);
+
+        // Submit chunks out of order
+        let spots = SpotRange::new(1, 1);
+        ordered
+            .submit(
+                2,
+                vec![b"C".to_vec()],
+                vec![1],
+                SpotEnds::default(),
+                None,
+                spots,
+            )
+            .unwrap();
+        ordered
+            .submit(
+                0,
+                vec![b"A".to_vec()],
+                vec![1],
+                SpotEnds::default(),
+                None,
+                spots,
+            )
+            .unwrap();
+        assert_eq!(data.lock().unwrap().as_slice(), b"A");
+        ordered
+            .submit(
+                1,
+                vec![b"B".to_vec()],
+                vec![1],
+                SpotEnds::default(),
+                None,
+                spots,
+            )
+            .unwrap();
+
+        assert_eq!(data.lock().unwrap().as_slice(), b"ABC");
+    }
+
+    #[test]
+    fn ordered_writer_fails_after_abort() {
+        let inner: BoxedSegmentWriter = Box::new(DirectWriter {
+            segment_handles: vec![Box::new(std::io::empty())],
+        });
+        let ordered = OrderedWriter::new(inner, 1);
+        ordered.abort();
+        assert!(ordered
+            .submit(
+                0,
+                vec![Vec::new()],
+                vec![0],
+                SpotEnds::default(),
+                None,
+                SpotRange::new(1, 1)
+            )
+            .is_err());
+    }
+
+    // RotatingWriter::write_all_buffers tests
+    #[test]
+    fn rotating_writer_rotates_mates_together() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let outdir = temp_dir.path().to_str().unwrap();
+        let outputs = PartialOutputs::default();
+        let mut writer = RotatingWriter::new(
+            outdir,
+            "seg_",
+            Compression::Uncompressed.into(),
+            OutputFormat::Fasta,
+            1,
+            vec![Some("1".to_string()), Some("2".to_string()), None],
+            ChunkLimits::new(Some(2), None).unwrap(),
+            outputs.clone(),
+        );
+
+        // three batches of a single spot each, followed by a batch of two spots
+        for _ in 0..3 {
+            let mut buffers = vec![b">a\nA\n".to_vec(), b">a\nC\n".to_vec(), Vec::new()];
+            writer
+                .write_all_buffers(&mut buffers, &mut [1, 1, 0])
+                .unwrap();
+        }
+        let mut buffers = vec![
+            b">b\nA\n>c\nA\n".to_vec(),
+            b">b\nC\n>c\nC\n".to_vec(),
+            Vec::new(),
+        ];
+        writer
+            .write_all_buffers(&mut buffers, &mut [2, 2, 0])
+            .unwrap();
+        writer.finish().unwrap();
+        outputs.commit().unwrap();
+
+        let read = |name: &str| std::fs::read_to_string(temp_dir.path().join(name)).unwrap();
+        assert_eq!(read("seg_1.part0001.fa"), ">a\nA\n>a\nA\n");
+        assert_eq!(read("seg_2.part0001.fa"), ">a\nC\n>a\nC\n");
+        assert_eq!(read("seg_1.part0002.fa"), ">a\nA\n");
+        assert_eq!(read("seg_2.part0002.fa"), ">a\nC\n");
+        assert_eq!(read("seg_1.part0003.fa"), ">b\nA\n>c\nA\n");
+        assert!(!temp_dir.path().join("seg_1.part0004.fa").exists());
+    }
+
+    #[test]
+    fn rotating_writer_splits_buffers_at_spot_ends() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let outdir = temp_dir.path().to_str().unwrap();
+        let outputs = PartialOutputs::default();
+        let mut writer = RotatingWriter::new(
+            outdir,
+            "seg_",
+            Compression::Uncompressed.into(),
+            OutputFormat::Fasta,
+            1,
+            vec![Some("1".to_string()), Some("2".to_string())],
+            ChunkLimits::new(Some(2), None).unwrap(),
+            outputs.clone(),
+        );
+
+        // a single batch of five spots where the second spot only has a read in file 1
+        let mut buffers = vec![Vec::new(), Vec::new()];
+        let mut counts = vec![0, 0];
+        let mut ends = SpotEnds::new(true);
+        for (spot, mates) in [("1", 2), ("2", 1), ("3", 2), ("4", 2), ("5", 2)] {
+            for (idx, base) in ["A", "C"].iter().enumerate().take(mates) {
+                buffers[idx].extend_from_slice(format!(">{spot}\n{base}\n").as_bytes());
+                counts[idx] += 1;
+            }
+            ends.push(&buffers, &counts);
+        }
+        writer
+            .write_spots(&mut buffers, &mut counts, &ends)
+            .unwrap();
+        writer.finish().unwrap();
+        outputs.commit().unwrap();
+
+        let read = |name: &str| std::fs::read_to_string(temp_dir.path().join(name)).unwrap();
+        assert_eq!(read("seg_1.part0001.fa"), ">1\nA\n>2\nA\n");
+        assert_eq!(read("seg_2.part0001.fa"), ">1\nC\n");
+        assert_eq!(read("seg_1.part0002.fa"), ">3\nA\n>4\nA\n");
+        assert_eq!(read("seg_2.part0002.fa"), ">3\nC\n>4\nC\n");
+        assert_eq!(read("seg_1.part0003.fa"), ">5\nA\n");
+        assert_eq!(read("seg_2.part0003.fa"), ">5\nC\n");
+        assert!(buffers.iter().all(Vec::is_empty));
+    }
 }
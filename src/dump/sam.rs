@@ -0,0 +1,303 @@
+use std::io::Write;
+
+use anyhow::Result;
+
+use super::defline::Read;
+use crate::cli::TechTag;
+use crate::quality::PHRED33_OFFSET;
+
+/// The read is paired in sequencing
+pub const FLAG_PAIRED: u16 = 0x1;
+/// The read itself is unmapped
+pub const FLAG_UNMAPPED: u16 = 0x4;
+/// The mate is unmapped
+pub const FLAG_MATE_UNMAPPED: u16 = 0x8;
+/// The read is the first segment in the template
+pub const FLAG_FIRST: u16 = 0x40;
+/// The read is the last segment in the template
+pub const FLAG_LAST: u16 = 0x80;
+
+/// BAM bin of an unmapped read (reg2bin(-1, 0))
+const UNMAPPED_BIN: u16 = 4680;
+
+/// Sequence and quality of the technical segments of a spot stored as tags
+#[derive(Debug, Default)]
+pub struct TechTags {
+    seq: Vec<u8>,
+    qual: Vec<u8>,
+}
+impl TechTags {
+    /// Append a technical segment to the tag values
+    ///
+    /// Multiple segments are separated by '-' (sequence) and ' ' (quality) as in the SAM spec.
+    pub fn push(&mut self, seq: &[u8], qual: &[u8]) {
+        if !self.seq.is_empty() {
+            self.seq.push(b'-');
+            self.qual.push(b' ');
+        }
+        self.seq.extend_from_slice(seq);
+        self.qual.extend_from_slice(qual);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seq.is_empty()
+    }
+}
+
+/// Encodes unaligned reads as SAM or BAM records
+#[derive(Debug, Clone)]
+pub struct SamEncoder {
+    /// Encode as BAM (the BGZF compression is handled by the output handle)
+    binary: bool,
+    /// Read group of all records (derived from the accession)
+    read_group: String,
+    /// Tag pair used to store technical segments
+    tech_tag: Option<TechTag>,
+}
+impl SamEncoder {
+    pub fn new(binary: bool, accession: &str, tech_tag: Option<TechTag>) -> Self {
+        Self {
+            binary,
+            read_group: accession.to_string(),
+            tech_tag,
+        }
+    }
+
+    pub fn tech_tag(&self) -> Option<TechTag> {
+        self.tech_tag
+    }
+
+    fn header_text(&self) -> String {
+        format!(
+            "@HD\tVN:1.6\tSO:unsorted\n@RG\tID:{rg}\tSM:{rg}\n@PG\tID:xsra\tPN:xsra\tVN:{}\n",
+            env!("CARGO_PKG_VERSION"),
+            rg = self.read_group,
+        )
+    }
+
+    /// The SAM header (or BAM header block for binary output)
+    pub fn header(&self) -> Vec<u8> {
+        let text = self.header_text();
+        if !self.binary {
+            return text.into_bytes();
+        }
+        let mut header = Vec::with_capacity(text.len() + 12);
+        header.extend_from_slice(b"BAM\x01");
+        header.extend_from_slice(&(text.len() as i32).to_le_bytes());
+        header.extend_from_slice(text.as_bytes());
+        // no reference sequences
+        header.extend_from_slice(&0i32.to_le_bytes());
+        header
+    }
+
+    /// The query name shared by all segments of a spot
+    fn qname(&self, read: &Read<'_>) -> String {
+        format!("{}.{}", self.read_group, read.spot_id)
+    }
+
+    /// Write a single unaligned record
+    pub fn write_record(
+        &self,
+        buffer: &mut Vec<u8>,
+        read: &Read<'_>,
+        flag: u16,
+        tech: &TechTags,
+    ) -> Result<()> {
+        if self.binary {
+            self.write_bam_record(buffer, read, flag, tech);
+            Ok(())
+        } else {
+            self.write_sam_record(buffer, read, flag, tech)
+        }
+    }
+
+    fn write_sam_record(
+        &self,
+        buffer: &mut Vec<u8>,
+        read: &Read<'_>,
+        flag: u16,
+        tech: &TechTags,
+    ) -> Result<()> {
+        write!(
+            buffer,
+            "{}\t{flag}\t*\t0\t0\t*\t*\t0\t0\t",
+            self.qname(read)
+        )?;
+        buffer.write_all(if read.seq.is_empty() { b"*" } else { read.seq })?;
+        buffer.write_all(b"\t")?;
+        buffer.write_all(if read.qual.is_empty() {
+            b"*"
+        } else {
+            read.qual
+        })?;
+        write!(buffer, "\tRG:Z:{}", self.read_group)?;
+        if let (Some(tech_tag), false) = (self.tech_tag, tech.is_empty()) {
+            let (seq_tag, qual_tag) = tech_tag.tags();
+            write!(buffer, "\t{seq_tag}:Z:")?;
+            buffer.write_all(&tech.seq)?;
+            write!(buffer, "\t{qual_tag}:Z:")?;
+            buffer.write_all(&tech.qual)?;
+        }
+        writeln!(buffer)?;
+        Ok(())
+    }
+
+    fn write_bam_record(&self, buffer: &mut Vec<u8>, read: &Read<'_>, flag: u16, tech: &TechTags) {
+        let qname = self.qname(read);
+        let l_seq = read.seq.len();
+
+        // Reserve the block size and fill it in once the record is complete
+        let block_start = buffer.len();
+        buffer.extend_from_slice(&0i32.to_le_bytes());
+
+        buffer.extend_from_slice(&(-1i32).to_le_bytes()); // refID
+        buffer.extend_from_slice(&(-1i32).to_le_bytes()); // pos
+        buffer.push((qname.len() + 1) as u8); // l_read_name
+        buffer.push(255); // mapq
+        buffer.extend_from_slice(&UNMAPPED_BIN.to_le_bytes());
+        buffer.extend_from_slice(&0u16.to_le_bytes()); // n_cigar_op
+        buffer.extend_from_slice(&flag.to_le_bytes());
+        buffer.extend_from_slice(&(l_seq as u32).to_le_bytes());
+        buffer.extend_from_slice(&(-1i32).to_le_bytes()); // next_refID
+        buffer.extend_from_slice(&(-1i32).to_le_bytes()); // next_pos
+        buffer.extend_from_slice(&0i32.to_le_bytes()); // tlen
+
+        buffer.extend_from_slice(qname.as_bytes());
+        buffer.push(0);
+
+        // 4-bit encoded sequence (two bases per byte)
+        for pair in read.seq.chunks(2) {
+            let hi = encode_base(pair[0]) << 4;
+            let lo = pair.get(1).map_or(0, |&b| encode_base(b));
+            buffer.push(hi | lo);
+        }
+
+        // Raw phred scores (0xFF if absent)
+        if read.qual.len() == l_seq {
+            buffer.extend(read.qual.iter().map(|q| q.saturating_sub(PHRED33_OFFSET)));
+        } else {
+            buffer.resize(buffer.len() + l_seq, 0xFF);
+        }
+
+        push_bam_tag(buffer, b"RG", self.read_group.as_bytes());
+        if let (Some(tech_tag), false) = (self.tech_tag, tech.is_empty()) {
+            let (seq_tag, qual_tag) = tech_tag.tags();
+            push_bam_tag(buffer, seq_tag.as_bytes(), &tech.seq);
+            push_bam_tag(buffer, qual_tag.as_bytes(), &tech.qual);
+        }
+
+        let block_size = (buffer.len() - block_start - 4) as i32;
+        buffer[block_start..block_start + 4].copy_from_slice(&block_size.to_le_bytes());
+    }
+}
+
+/// Appends a NUL-terminated string (`Z`) tag to a BAM record
+fn push_bam_tag(buffer: &mut Vec<u8>, tag: &[u8], value: &[u8]) {
+    buffer.extend_from_slice(tag);
+    buffer.push(b'Z');
+    buffer.extend_from_slice(value);
+    buffer.push(0);
+}
+
+/// Encodes a nucleotide into its 4-bit BAM representation (`=ACMGRSVTWYHKDBN`)
+fn encode_base(base: u8) -> u8 {
+    match base.to_ascii_uppercase() {
+        b'=' => 0,
+        b'A' => 1,
+        b'C' => 2,
+        b'M' => 3,
+        b'G' => 4,
+        b'R' => 5,
+        b'S' => 6,
+        b'V' => 7,
+        b'T' => 8,
+        b'W' => 9,
+        b'Y' => 10,
+        b'H' => 11,
+        b'K' => 12,
+        b'D' => 13,
+        b'B' => 14,
+        _ => 15,
+    }
+}
+
+/// The FLAG of an unaligned read given its position in the template
+pub fn unaligned_flag(mate: Option<usize>) -> u16 {
+    match mate {
+        Some(0) => FLAG_PAIRED | FLAG_UNMAPPED | FLAG_MATE_UNMAPPED | FLAG_FIRST,
+        Some(_) => FLAG_PAIRED | FLAG_UNMAPPED | FLAG_MATE_UNMAPPED | FLAG_LAST,
+        None => FLAG_UNMAPPED,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read<'a>(seq: &'a [u8], qual: &'a [u8]) -> Read<'a> {
+        Read {
+            spot_id: 3,
+            sid: 0,
+            seq,
+            qual,
+        }
+    }
+
+    // unaligned_flag tests
+    #[test]
+    fn unaligned_flag_marks_mates() {
+        assert_eq!(unaligned_flag(Some(0)), 77);
+        assert_eq!(unaligned_flag(Some(1)), 141);
+        assert_eq!(unaligned_flag(None), 4);
+    }
+
+    // SamEncoder::write_record tests
+    #[test]
+    fn sam_record_with_tech_tags() {
+        let encoder = SamEncoder::new(false, "SRR1", Some(TechTag::Bc));
+        let mut tech = TechTags::default();
+        tech.push(b"AAC", b"III");
+        tech.push(b"GT", b"##");
+
+        let mut buffer = Vec::new();
+        encoder
+            .write_record(&mut buffer, &read(b"ACGT", b"IIII"), 77, &tech)
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "SRR1.3\t77\t*\t0\t0\t*\t*\t0\t0\tACGT\tIIII\tRG:Z:SRR1\tBC:Z:AAC-GT\tQT:Z:III ##\n"
+        );
+    }
+
+    #[test]
+    fn bam_record_layout() {
+        let encoder = SamEncoder::new(true, "SRR1", None);
+        let mut buffer = Vec::new();
+        encoder
+            .write_record(&mut buffer, &read(b"ACG", b"I#I"), 4, &TechTags::default())
+            .unwrap();
+
+        let block_size = i32::from_le_bytes(buffer[0..4].try_into().unwrap()) as usize;
+        assert_eq!(block_size, buffer.len() - 4);
+        // l_read_name includes the NUL terminator
+        assert_eq!(buffer[12], b"SRR1.3".len() as u8 + 1);
+        // flag and l_seq
+        assert_eq!(u16::from_le_bytes([buffer[18], buffer[19]]), 4);
+        assert_eq!(u32::from_le_bytes(buffer[20..24].try_into().unwrap()), 3);
+        // packed sequence follows the read name
+        let seq_start = 36 + b"SRR1.3".len() + 1;
+        assert_eq!(&buffer[seq_start..seq_start + 2], &[0x12, 0x40]);
+        assert_eq!(&buffer[seq_start + 2..seq_start + 5], &[40, 2, 40]);
+        assert!(buffer.ends_with(b"RGZSRR1\0"));
+    }
+
+    #[test]
+    fn bam_header_layout() {
+        let encoder = SamEncoder::new(true, "SRR1", None);
+        let header = encoder.header();
+        assert!(header.starts_with(b"BAM\x01"));
+        let l_text = i32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+        assert_eq!(header.len(), 8 + l_text + 4);
+        assert!(header.ends_with(&0i32.to_le_bytes()));
+    }
+}
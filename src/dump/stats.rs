@@ -1,74 +1,85 @@
 use std::io::Result;
 use std::io::Write;
 use std::ops::Add;
+use std::time::Duration;
 
-#[derive(Debug, Clone)]
+use serde::Serialize;
+
+use super::filter::FilterReason;
+use crate::cli::{SpotFilter, StatsFormat};
+
+#[derive(Debug, Clone, Serialize)]
 pub struct ProcessStatistics {
     pub num_spots: u64,
     pub num_reads: u64,
+    /// Number of bases of the written reads
+    pub num_bases: u64,
     /// Number of written reads per segment
     pub reads_per_segment: Vec<u64>,
+    /// Number of written bases per segment
+    pub bases_per_segment: Vec<u64>,
     /// Number of reads filtered by size by segment
     pub filter_size: Vec<u64>,
     /// Number of reads filtered by biological/technical type by segment
     pub filter_type: Vec<u64>,
+    /// Number of reads filtered by N content by segment
+    pub filter_n: Vec<u64>,
+    /// Number of reads filtered by mean quality by segment
+    pub filter_quality: Vec<u64>,
+    /// Number of reads filtered by expected errors by segment
+    pub filter_errors: Vec<u64>,
+    /// Number of reads filtered by low complexity (DUST) by segment
+    pub filter_complexity: Vec<u64>,
+    /// Number of written reads per output handle
+    pub reads_per_output: Vec<u64>,
+    /// Number of spots dropped because any included segment failed a filter
+    pub filter_spot_any: u64,
+    /// Number of spots dropped because all included segments failed a filter
+    pub filter_spot_all: u64,
+    /// Number of bases removed by trimming by segment
+    pub trimmed_bases: Vec<u64>,
 }
 impl Default for ProcessStatistics {
     fn default() -> Self {
         Self {
             num_spots: 0,
             num_reads: 0,
+            num_bases: 0,
             reads_per_segment: vec![0; 4],
+            bases_per_segment: vec![0; 4],
             filter_size: vec![0; 4],
             filter_type: vec![0; 4],
+            filter_n: vec![0; 4],
+            filter_quality: vec![0; 4],
+            filter_errors: vec![0; 4],
+            filter_complexity: vec![0; 4],
+            reads_per_output: Vec::new(),
+            filter_spot_any: 0,
+            filter_spot_all: 0,
+            trimmed_bases: vec![0; 4],
         }
     }
 }
 impl Add for ProcessStatistics {
     type Output = Self;
 
-    fn add(mut self, other: Self) -> Self {
-        let num_spots = self.num_spots + other.num_spots;
-        let num_reads = self.num_reads + other.num_reads;
-
-        // Resize vectors to match the longest one
-        if self.reads_per_segment.len() < other.reads_per_segment.len() {
-            self.reads_per_segment
-                .resize(other.reads_per_segment.len(), 0);
-        }
-        if self.filter_size.len() < other.filter_size.len() {
-            self.filter_size.resize(other.filter_size.len(), 0);
-        }
-        if self.filter_type.len() < other.filter_type.len() {
-            self.filter_type.resize(other.filter_type.len(), 0);
-        }
-
-        // Sum vectors
-        let reads_per_segment = self
-            .reads_per_segment
-            .iter()
-            .zip(other.reads_per_segment.iter())
-            .map(|(a, b)| a + b)
-            .collect();
-        let filter_size = self
-            .filter_size
-            .iter()
-            .zip(other.filter_size.iter())
-            .map(|(a, b)| a + b)
-            .collect();
-        let filter_type = self
-            .filter_type
-            .iter()
-            .zip(other.filter_type.iter())
-            .map(|(a, b)| a + b)
-            .collect();
-
+    fn add(self, other: Self) -> Self {
         ProcessStatistics {
-            num_spots,
-            num_reads,
-            reads_per_segment,
-            filter_size,
-            filter_type,
+            num_spots: self.num_spots + other.num_spots,
+            num_reads: self.num_reads + other.num_reads,
+            num_bases: self.num_bases + other.num_bases,
+            reads_per_segment: merge_counts(self.reads_per_segment, &other.reads_per_segment),
+            bases_per_segment: merge_counts(self.bases_per_segment, &other.bases_per_segment),
+            filter_size: merge_counts(self.filter_size, &other.filter_size),
+            filter_type: merge_counts(self.filter_type, &other.filter_type),
+            filter_n: merge_counts(self.filter_n, &other.filter_n),
+            filter_quality: merge_counts(self.filter_quality, &other.filter_quality),
+            filter_errors: merge_counts(self.filter_errors, &other.filter_errors),
+            filter_complexity: merge_counts(self.filter_complexity, &other.filter_complexity),
+            reads_per_output: merge_counts(self.reads_per_output, &other.reads_per_output),
+            filter_spot_any: self.filter_spot_any + other.filter_spot_any,
+            filter_spot_all: self.filter_spot_all + other.filter_spot_all,
+            trimmed_bases: merge_counts(self.trimmed_bases, &other.trimmed_bases),
         }
     }
 }
@@ -76,12 +87,39 @@ impl ProcessStatistics {
     pub fn inc_spots(&mut self) {
         self.num_spots += 1;
     }
-    pub fn inc_reads(&mut self, seg_id: usize) {
+    pub fn inc_reads(&mut self, seg_id: usize, num_bases: usize) {
         self.num_reads += 1;
+        self.num_bases += num_bases as u64;
         if seg_id >= self.reads_per_segment.len() {
             self.reads_per_segment.resize(seg_id + 1, 0);
         }
         self.reads_per_segment[seg_id] += 1;
+        if seg_id >= self.bases_per_segment.len() {
+            self.bases_per_segment.resize(seg_id + 1, 0);
+        }
+        self.bases_per_segment[seg_id] += num_bases as u64;
+    }
+    pub fn inc_output(&mut self, output_id: usize) {
+        if output_id >= self.reads_per_output.len() {
+            self.reads_per_output.resize(output_id + 1, 0);
+        }
+        self.reads_per_output[output_id] += 1;
+    }
+    pub fn inc_filter(&mut self, reason: FilterReason, seg_id: usize) {
+        match reason {
+            FilterReason::Size => self.inc_filter_size(seg_id),
+            FilterReason::NContent => inc_count(&mut self.filter_n, seg_id),
+            FilterReason::MeanQuality => inc_count(&mut self.filter_quality, seg_id),
+            FilterReason::ExpectedErrors => inc_count(&mut self.filter_errors, seg_id),
+            FilterReason::LowComplexity => inc_count(&mut self.filter_complexity, seg_id),
+        }
+    }
+    pub fn inc_filter_spot(&mut self, policy: SpotFilter) {
+        match policy {
+            SpotFilter::Any => self.filter_spot_any += 1,
+            SpotFilter::All => self.filter_spot_all += 1,
+            SpotFilter::Segment => {}
+        }
     }
     pub fn inc_filter_size(&mut self, seg_id: usize) {
         if seg_id >= self.filter_size.len() {
@@ -89,6 +127,12 @@ impl ProcessStatistics {
         }
         self.filter_size[seg_id] += 1;
     }
+    pub fn add_trimmed(&mut self, seg_id: usize, num_bases: usize) {
+        if seg_id >= self.trimmed_bases.len() {
+            self.trimmed_bases.resize(seg_id + 1, 0);
+        }
+        self.trimmed_bases[seg_id] += num_bases as u64;
+    }
     pub fn inc_filter_type(&mut self, seg_id: usize) {
         if seg_id >= self.filter_type.len() {
             self.filter_type.resize(seg_id + 1, 0);
@@ -98,6 +142,7 @@ impl ProcessStatistics {
     pub fn pprint<W: Write>(&self, wtr: &mut W) -> Result<()> {
         writeln!(wtr, "Number of spots processed: {}", self.num_spots)?;
         writeln!(wtr, "Number of reads written: {}", self.num_reads)?;
+        writeln!(wtr, "Number of bases written: {}", self.num_bases)?;
 
         if sum_slice(&self.reads_per_segment) > 0 {
             writeln!(wtr, "Reads written per segment:")?;
@@ -117,6 +162,124 @@ impl ProcessStatistics {
                 writeln!(wtr, "  Segment {i}: {count}")?;
             }
         }
+        for (label, counts) in [
+            ("N content", &self.filter_n),
+            ("mean quality", &self.filter_quality),
+            ("expected errors", &self.filter_errors),
+            ("low complexity", &self.filter_complexity),
+        ] {
+            if sum_slice(counts) > 0 {
+                writeln!(wtr, "Filtered reads by {label}:")?;
+                for (i, &count) in counts.iter().enumerate() {
+                    writeln!(wtr, "  Segment {i}: {count}")?;
+                }
+            }
+        }
+        if sum_slice(&self.trimmed_bases) > 0 {
+            writeln!(wtr, "Trimmed bases:")?;
+            for (i, &count) in self.trimmed_bases.iter().enumerate() {
+                writeln!(wtr, "  Segment {i}: {count}")?;
+            }
+        }
+        if self.filter_spot_any > 0 {
+            writeln!(
+                wtr,
+                "Filtered spots (any segment failed): {}",
+                self.filter_spot_any
+            )?;
+        }
+        if self.filter_spot_all > 0 {
+            writeln!(
+                wtr,
+                "Filtered spots (all segments failed): {}",
+                self.filter_spot_all
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Final statistics of a dump along with its runtime
+#[derive(Debug, Serialize)]
+pub struct StatsReport<'a> {
+    #[serde(flatten)]
+    pub stats: &'a ProcessStatistics,
+    pub elapsed_secs: f64,
+    pub spots_per_sec: f64,
+    pub bases_per_sec: f64,
+}
+impl<'a> StatsReport<'a> {
+    pub fn new(stats: &'a ProcessStatistics, elapsed: Duration) -> Self {
+        let elapsed_secs = elapsed.as_secs_f64();
+        let rate = |count: u64| {
+            if elapsed_secs > 0.0 {
+                count as f64 / elapsed_secs
+            } else {
+                0.0
+            }
+        };
+        Self {
+            stats,
+            elapsed_secs,
+            spots_per_sec: rate(stats.num_spots),
+            bases_per_sec: rate(stats.num_bases),
+        }
+    }
+
+    pub fn write<W: Write>(&self, wtr: &mut W, format: StatsFormat) -> Result<()> {
+        match format {
+            StatsFormat::Text => {
+                self.stats.pprint(wtr)?;
+                writeln!(
+                    wtr,
+                    "Elapsed time: {:.2}s ({:.0} spots/s)",
+                    self.elapsed_secs, self.spots_per_sec
+                )?;
+            }
+            StatsFormat::Json => {
+                serde_json::to_writer_pretty(&mut *wtr, self)?;
+                writeln!(wtr)?;
+            }
+            StatsFormat::Tsv => self.write_tsv(wtr)?,
+        }
+        Ok(())
+    }
+
+    /// Writes one `metric  segment  value` row per statistic (totals have segment `-`)
+    fn write_tsv<W: Write>(&self, wtr: &mut W) -> Result<()> {
+        let stats = self.stats;
+        writeln!(wtr, "metric\tsegment\tvalue")?;
+        for (metric, value) in [
+            ("spots", stats.num_spots),
+            ("reads", stats.num_reads),
+            ("bases", stats.num_bases),
+            ("filtered_spots_any", stats.filter_spot_any),
+            ("filtered_spots_all", stats.filter_spot_all),
+        ] {
+            writeln!(wtr, "{metric}\t-\t{value}")?;
+        }
+        for (metric, counts) in [
+            ("reads", &stats.reads_per_segment),
+            ("bases", &stats.bases_per_segment),
+            ("filtered_size", &stats.filter_size),
+            ("filtered_type", &stats.filter_type),
+            ("filtered_n_content", &stats.filter_n),
+            ("filtered_mean_quality", &stats.filter_quality),
+            ("filtered_expected_errors", &stats.filter_errors),
+            ("filtered_low_complexity", &stats.filter_complexity),
+            ("trimmed_bases", &stats.trimmed_bases),
+        ] {
+            for (i, count) in counts.iter().enumerate() {
+                writeln!(wtr, "{metric}\t{i}\t{count}")?;
+            }
+        }
+        for (metric, value) in [
+            ("elapsed_secs", self.elapsed_secs),
+            ("spots_per_sec", self.spots_per_sec),
+            ("bases_per_sec", self.bases_per_sec),
+        ] {
+            writeln!(wtr, "{metric}\t-\t{value:.3}")?;
+        }
         Ok(())
     }
 }
@@ -125,6 +288,25 @@ fn sum_slice(vec: &[u64]) -> u64 {
     vec.iter().sum()
 }
 
+/// Element-wise sum of two count vectors (the shorter one is padded with zeros)
+fn merge_counts(mut lhs: Vec<u64>, rhs: &[u64]) -> Vec<u64> {
+    if lhs.len() < rhs.len() {
+        lhs.resize(rhs.len(), 0);
+    }
+    for (a, b) in lhs.iter_mut().zip(rhs) {
+        *a += b;
+    }
+    lhs
+}
+
+/// Increments the count of a segment (resizing if required)
+fn inc_count(counts: &mut Vec<u64>, seg_id: usize) {
+    if seg_id >= counts.len() {
+        counts.resize(seg_id + 1, 0);
+    }
+    counts[seg_id] += 1;
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -135,25 +317,59 @@ mod tests {
         let stats1 = ProcessStatistics {
             num_spots: 10,
             num_reads: 20,
+            num_bases: 300,
             reads_per_segment: vec![1, 2],
+            bases_per_segment: vec![100, 200],
             filter_size: vec![3, 4],
             filter_type: vec![5, 6],
+            filter_n: vec![1],
+            filter_quality: vec![],
+            filter_errors: vec![],
+            filter_complexity: vec![],
+            reads_per_output: vec![3],
+            filter_spot_any: 1,
+            filter_spot_all: 0,
+            trimmed_bases: vec![10],
         };
         let stats2 = ProcessStatistics {
             num_spots: 5,
             num_reads: 10,
+            num_bases: 400,
             reads_per_segment: vec![1, 1, 1, 1],
+            bases_per_segment: vec![100, 100, 100, 100],
             filter_size: vec![2, 2, 2],
             filter_type: vec![3, 3, 3, 3, 3],
+            filter_n: vec![0, 2],
+            filter_quality: vec![],
+            filter_errors: vec![],
+            filter_complexity: vec![],
+            reads_per_output: vec![1, 2],
+            filter_spot_any: 2,
+            filter_spot_all: 3,
+            trimmed_bases: vec![1, 2],
         };
 
         let result = stats1.clone() + stats2.clone();
 
         assert_eq!(result.num_spots, 15);
         assert_eq!(result.num_reads, 30);
+        assert_eq!(result.num_bases, 700);
+        assert_eq!(result.reads_per_segment, vec![2, 3, 1, 1]);
+        assert_eq!(result.bases_per_segment, vec![200, 300, 100, 100]);
+        assert_eq!(result.filter_size, vec![5, 6, 2]);
+        assert_eq!(result.filter_type, vec![8, 9, 3, 3, 3]);
+        assert_eq!(result.filter_n, vec![1, 2]);
+        assert_eq!(result.reads_per_output, vec![4, 2]);
+        assert_eq!(result.filter_spot_any, 3);
+        assert_eq!(result.filter_spot_all, 3);
+        assert_eq!(result.trimmed_bases, vec![11, 2]);
+
+        // the longer side is kept whole in either order
+        let result = stats2 + stats1;
         assert_eq!(result.reads_per_segment, vec![2, 3, 1, 1]);
         assert_eq!(result.filter_size, vec![5, 6, 2]);
         assert_eq!(result.filter_type, vec![8, 9, 3, 3, 3]);
+        assert_eq!(result.reads_per_output, vec![4, 2]);
     }
 
     #[test]
@@ -170,10 +386,12 @@ mod tests {
     fn test_inc_reads_with_resize() {
         let mut stats = ProcessStatistics::default();
         // set seg_id > initial len of 4 to trigger resize
-        stats.inc_reads(5);
+        stats.inc_reads(5, 10);
         assert_eq!(stats.num_reads, 1);
+        assert_eq!(stats.num_bases, 10);
         assert_eq!(stats.reads_per_segment.len(), 6);
         assert_eq!(stats.reads_per_segment[5], 1);
+        assert_eq!(stats.bases_per_segment[5], 10);
     }
 
     // ProcessStatistics::inc_filter_size tests
@@ -186,6 +404,16 @@ mod tests {
         assert_eq!(stats.filter_size[5], 1);
     }
 
+    // ProcessStatistics::add_trimmed tests
+    #[test]
+    fn test_add_trimmed_with_resize() {
+        let mut stats = ProcessStatistics::default();
+        stats.add_trimmed(5, 3);
+        stats.add_trimmed(5, 2);
+        assert_eq!(stats.trimmed_bases.len(), 6);
+        assert_eq!(stats.trimmed_bases[5], 5);
+    }
+
     // ProcessStatistics::inc_filter_type tests
     #[test]
     fn test_inc_filter_type_with_resize() {
@@ -202,9 +430,19 @@ mod tests {
         let stats = ProcessStatistics {
             num_spots: 100,
             num_reads: 90,
+            num_bases: 9000,
             reads_per_segment: vec![40, 50],
+            bases_per_segment: vec![4000, 5000],
             filter_size: vec![5, 0],
             filter_type: vec![0, 5],
+            filter_n: vec![0, 0],
+            filter_quality: vec![0, 0],
+            filter_errors: vec![3, 0],
+            filter_complexity: vec![0, 0],
+            reads_per_output: vec![90],
+            filter_spot_any: 2,
+            filter_spot_all: 0,
+            trimmed_bases: vec![12, 0],
         };
 
         let mut buffer = Vec::new();
@@ -220,5 +458,72 @@ mod tests {
         assert!(output.contains("  Segment 0: 5"));
         assert!(output.contains("Filtered reads by type:"));
         assert!(output.contains("  Segment 1: 5"));
+        assert!(output.contains("Filtered reads by expected errors:"));
+        assert!(!output.contains("Filtered reads by N content:"));
+        assert!(output.contains("Trimmed bases:"));
+        assert!(output.contains("  Segment 0: 12"));
+        assert!(output.contains("Filtered spots (any segment failed): 2"));
+        assert!(!output.contains("Filtered spots (all segments failed)"));
+    }
+
+    // StatsReport tests
+    #[test]
+    fn test_stats_report_json() {
+        let mut stats = ProcessStatistics::default();
+        stats.inc_spots();
+        stats.inc_reads(1, 150);
+        stats.inc_filter(FilterReason::NContent, 0);
+        let report = StatsReport::new(&stats, Duration::from_secs(2));
+
+        let mut buffer = Vec::new();
+        report.write(&mut buffer, StatsFormat::Json).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&buffer).unwrap();
+
+        assert_eq!(value["num_spots"], 1);
+        assert_eq!(value["bases_per_segment"][1], 150);
+        assert_eq!(value["filter_n"][0], 1);
+        assert_eq!(value["elapsed_secs"], 2.0);
+        assert_eq!(value["bases_per_sec"], 75.0);
+    }
+
+    #[test]
+    fn test_stats_report_tsv() {
+        let mut stats = ProcessStatistics::default();
+        stats.inc_spots();
+        stats.inc_reads(1, 150);
+        let report = StatsReport::new(&stats, Duration::from_secs(2));
+
+        let mut buffer = Vec::new();
+        report.write(&mut buffer, StatsFormat::Tsv).unwrap();
+        let output = String::from_utf8(buffer).unwrap();
+
+        assert!(output.starts_with("metric\tsegment\tvalue\n"));
+        assert!(output.contains("spots\t-\t1\n"));
+        assert!(output.contains("bases\t1\t150\n"));
+        assert!(output.contains("spots_per_sec\t-\t0.500\n"));
+        assert!(output.lines().all(|line| line.split('\t').count() == 3));
+    }
+
+    // ProcessStatistics::inc_filter tests
+    #[test]
+    fn test_inc_filter_by_reason() {
+        let mut stats = ProcessStatistics::default();
+        stats.inc_filter(FilterReason::Size, 0);
+        stats.inc_filter(FilterReason::LowComplexity, 5);
+        assert_eq!(stats.filter_size[0], 1);
+        assert_eq!(stats.filter_complexity.len(), 6);
+        assert_eq!(stats.filter_complexity[5], 1);
+    }
+
+    // ProcessStatistics::inc_filter_spot tests
+    #[test]
+    fn test_inc_filter_spot_by_policy() {
+        let mut stats = ProcessStatistics::default();
+        stats.inc_filter_spot(SpotFilter::Any);
+        stats.inc_filter_spot(SpotFilter::All);
+        stats.inc_filter_spot(SpotFilter::All);
+        stats.inc_filter_spot(SpotFilter::Segment);
+        assert_eq!(stats.filter_spot_any, 1);
+        assert_eq!(stats.filter_spot_all, 2);
     }
 }
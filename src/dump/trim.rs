@@ -0,0 +1,121 @@
+use std::ops::Range;
+
+use crate::cli::TrimOptions;
+use crate::quality::PHRED33_OFFSET;
+
+/// Returns the range of a read retained after trimming
+///
+/// Trims are applied in order: trailing Ns, leading and trailing low-quality bases,
+/// then the sliding window (scanning from the 5' end and cutting at the start of the
+/// first failing window). Quality trims are skipped for reads without quality scores.
+pub fn trim_range(trim_opts: &TrimOptions, seq: &[u8], qual: &[u8]) -> Range<usize> {
+    let mut start = 0;
+    let mut end = seq.len();
+
+    if trim_opts.trim_trailing_n {
+        while end > start && seq[end - 1].eq_ignore_ascii_case(&b'N') {
+            end -= 1;
+        }
+    }
+
+    if qual.len() != seq.len() {
+        return start..end;
+    }
+    let score = |idx: usize| qual[idx].saturating_sub(PHRED33_OFFSET) as usize;
+
+    if let Some(min_qual) = trim_opts.trim_leading {
+        while start < end && score(start) < min_qual as usize {
+            start += 1;
+        }
+    }
+    if let Some(min_qual) = trim_opts.trim_trailing {
+        while end > start && score(end - 1) < min_qual as usize {
+            end -= 1;
+        }
+    }
+
+    if let Some(window) = trim_opts.trim_window {
+        // Reads shorter than the window are evaluated as a whole
+        let size = window.size.min(end - start);
+        if size > 0 {
+            let threshold = window.quality as usize * size;
+            let mut sum: usize = (start..start + size).map(score).sum();
+            let mut pos = start;
+            loop {
+                if sum < threshold {
+                    end = pos;
+                    break;
+                }
+                if pos + size >= end {
+                    break;
+                }
+                sum = sum + score(pos + size) - score(pos);
+                pos += 1;
+            }
+        }
+    }
+
+    start..end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    fn opts() -> TrimOptions {
+        TrimOptions::default()
+    }
+
+    // trim_range tests
+    #[test]
+    fn no_trimming_by_default() {
+        assert_eq!(trim_range(&opts(), b"ACGTN", b"#####"), 0..5);
+    }
+
+    #[test]
+    fn trailing_n() {
+        let trim_opts = TrimOptions {
+            trim_trailing_n: true,
+            ..opts()
+        };
+        assert_eq!(trim_range(&trim_opts, b"ACGTnN", b"IIIIII"), 0..4);
+        assert_eq!(trim_range(&trim_opts, b"NNN", b""), 0..0);
+    }
+
+    #[test]
+    fn leading_and_trailing_quality() {
+        let trim_opts = TrimOptions {
+            trim_leading: Some(20),
+            trim_trailing: Some(20),
+            ..opts()
+        };
+        // Q2, Q2, Q40, Q2, Q40, Q2
+        assert_eq!(trim_range(&trim_opts, b"ACGTAC", b"##I#I#"), 2..5);
+    }
+
+    #[test]
+    fn sliding_window() {
+        let trim_opts = TrimOptions::parse_from(["xsra", "--trim-window", "2:20"]);
+        // the window at position 3 (Q2, Q2) is the first below Q20
+        assert_eq!(trim_range(&trim_opts, b"ACGTACGT", b"III##III"), 0..3);
+        // a single low base is averaged out
+        assert_eq!(trim_range(&trim_opts, b"ACGTACGT", b"III#IIII"), 0..8);
+    }
+
+    #[test]
+    fn sliding_window_on_short_read() {
+        let trim_opts = TrimOptions::parse_from(["xsra", "--trim-window", "4:20"]);
+        assert_eq!(trim_range(&trim_opts, b"AC", b"##"), 0..0);
+        assert_eq!(trim_range(&trim_opts, b"AC", b"II"), 0..2);
+    }
+
+    #[test]
+    fn quality_trims_skipped_without_quality() {
+        let trim_opts = TrimOptions {
+            trim_leading: Some(20),
+            ..opts()
+        };
+        assert_eq!(trim_range(&trim_opts, b"ACGT", b""), 0..4);
+    }
+}
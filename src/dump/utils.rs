@@ -1,52 +1,48 @@
 use std::io::Write;
 
 use anyhow::{bail, Result};
-use ncbi_vdb_sys::Segment;
 
+use super::defline::{Defline, Read};
 use crate::cli::OutputFormat;
 
-pub fn write_segment_to_buffer_set(
+/// Writes a read to the buffer of the given output handle
+pub fn write_read_to_buffer_set(
     buffers: &mut [Vec<u8>],
-    segment: &Segment<'_>,
+    output_id: usize,
+    read: &Read<'_>,
     format: OutputFormat,
+    defline: &Defline,
 ) -> Result<()> {
-    if buffers.len() == 1 {
-        // Interleaved output - single output handle
-        let buffer = &mut buffers[0];
-        match format {
-            OutputFormat::Fasta => write_fasta(buffer, segment)?,
-            OutputFormat::Fastq => write_fastq(buffer, segment)?,
-        }
-        Ok(())
-    } else {
-        if segment.sid() >= buffers.len() {
-            bail!(
-                "Provided Segment ID: {} is above the expected 4-segment counts",
-                segment.sid()
-            );
-        }
-        let seg_id = segment.sid();
-        let buffer = &mut buffers[seg_id];
-        match format {
-            OutputFormat::Fasta => write_fasta(buffer, segment)?,
-            OutputFormat::Fastq => write_fastq(buffer, segment)?,
+    if output_id >= buffers.len() {
+        bail!(
+            "Provided Segment ID: {} is above the expected 4-segment counts",
+            read.sid
+        );
+    }
+    let buffer = &mut buffers[output_id];
+    match format {
+        OutputFormat::Fasta => write_fasta(buffer, read, defline)?,
+        OutputFormat::Fastq => write_fastq(buffer, read, defline)?,
+        OutputFormat::Sam | OutputFormat::Bam | OutputFormat::Parquet | OutputFormat::ArrowIpc => {
+            bail!("{format:?} records must be written with a dedicated encoder")
         }
-        Ok(())
     }
+    Ok(())
 }
 
-pub fn write_fastq<W: Write>(wtr: &mut W, segment: &Segment<'_>) -> Result<()> {
-    writeln!(wtr, "@{}.{}", segment.rid(), segment.sid())?;
-    wtr.write_all(segment.seq())?;
-    writeln!(wtr, "\n+")?;
-    wtr.write_all(segment.qual())?;
+pub fn write_fastq<W: Write>(wtr: &mut W, read: &Read<'_>, defline: &Defline) -> Result<()> {
+    defline.write_seq(wtr, '@', read)?;
+    wtr.write_all(read.seq)?;
+    writeln!(wtr)?;
+    defline.write_qual(wtr, read)?;
+    wtr.write_all(read.qual)?;
     writeln!(wtr)?;
     Ok(())
 }
 
-pub fn write_fasta<W: Write>(wtr: &mut W, segment: &Segment<'_>) -> Result<()> {
-    writeln!(wtr, ">{}.{}", segment.rid(), segment.sid())?;
-    wtr.write_all(segment.seq())?;
+pub fn write_fasta<W: Write>(wtr: &mut W, read: &Read<'_>, defline: &Defline) -> Result<()> {
+    defline.write_seq(wtr, '>', read)?;
+    wtr.write_all(read.seq)?;
     writeln!(wtr)?;
     Ok(())
 }
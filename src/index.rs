@@ -0,0 +1,329 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::output::OutputStream;
+
+/// Size of the BGZF block header up to (and including) the BSIZE field
+const BGZF_HEADER_SIZE: usize = 18;
+
+/// Path of the BGZF block index of an output
+pub fn gzi_path(path: &str) -> String {
+    format!("{path}.gzi")
+}
+
+/// Path of the FASTA index of an output
+pub fn fai_path(path: &str) -> String {
+    format!("{path}.fai")
+}
+
+/// Removes the indices of an output (if any were written)
+pub fn remove(path: &str) -> Result<()> {
+    for index in [gzi_path(path), fai_path(path)] {
+        if Path::new(&index).exists() {
+            std::fs::remove_file(index)?;
+        }
+    }
+    Ok(())
+}
+
+/// Records the block offsets of the BGZF stream written to the inner (file) handle
+///
+/// The index is written in the `.gzi` layout of `bgzip -i` once the writer is finished:
+/// the number of entries followed by the compressed and uncompressed offset of each block
+/// after the first one (all little-endian u64).
+pub struct GziWriter<W: Write> {
+    inner: W,
+    index: File,
+    /// (compressed, uncompressed) offsets of the block starts
+    entries: Vec<(u64, u64)>,
+    compressed: u64,
+    uncompressed: u64,
+    /// Header of the current block
+    header: [u8; BGZF_HEADER_SIZE],
+    /// Trailing ISIZE field of the current block
+    isize_bytes: [u8; 4],
+    /// Bytes of the current block seen so far
+    block_pos: usize,
+    /// Total size of the current block (known once its header is complete)
+    block_size: Option<usize>,
+}
+impl<W: Write> GziWriter<W> {
+    pub fn new(inner: W, index: File) -> Self {
+        Self {
+            inner,
+            index,
+            entries: Vec::new(),
+            compressed: 0,
+            uncompressed: 0,
+            header: [0; BGZF_HEADER_SIZE],
+            isize_bytes: [0; 4],
+            block_pos: 0,
+            block_size: None,
+        }
+    }
+
+    /// Follows the block boundaries across the written bytes
+    fn scan(&mut self, mut buf: &[u8]) -> std::io::Result<()> {
+        while !buf.is_empty() {
+            let Some(block_size) = self.block_size else {
+                let n = (BGZF_HEADER_SIZE - self.block_pos).min(buf.len());
+                self.header[self.block_pos..self.block_pos + n].copy_from_slice(&buf[..n]);
+                self.block_pos += n;
+                buf = &buf[n..];
+                if self.block_pos == BGZF_HEADER_SIZE {
+                    if self.header[..4] != [31, 139, 8, 4] || self.header[12..14] != *b"BC" {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::InvalidData,
+                            "BGZF block index expects BGZF blocks",
+                        ));
+                    }
+                    let bsize = u16::from_le_bytes([self.header[16], self.header[17]]);
+                    self.block_size = Some(bsize as usize + 1);
+                }
+                continue;
+            };
+
+            let n = (block_size - self.block_pos).min(buf.len());
+            // keep the bytes overlapping the trailing ISIZE field
+            let isize_start = block_size - 4;
+            for (i, &byte) in buf[..n].iter().enumerate() {
+                let pos = self.block_pos + i;
+                if pos >= isize_start {
+                    self.isize_bytes[pos - isize_start] = byte;
+                }
+            }
+            self.block_pos += n;
+            buf = &buf[n..];
+
+            if self.block_pos == block_size {
+                let data_size = u32::from_le_bytes(self.isize_bytes) as u64;
+                self.compressed += block_size as u64;
+                self.uncompressed += data_size;
+                // the empty EOF block does not start any data
+                if data_size > 0 {
+                    self.entries.push((self.compressed, self.uncompressed));
+                }
+                self.block_pos = 0;
+                self.block_size = None;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_index(&mut self) -> std::io::Result<()> {
+        let mut wtr = BufWriter::new(&mut self.index);
+        wtr.write_all(&(self.entries.len() as u64).to_le_bytes())?;
+        for (compressed, uncompressed) in &self.entries {
+            wtr.write_all(&compressed.to_le_bytes())?;
+            wtr.write_all(&uncompressed.to_le_bytes())?;
+        }
+        wtr.flush()
+    }
+}
+impl<W: Write> Write for GziWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let num_written = self.inner.write(buf)?;
+        self.scan(&buf[..num_written])?;
+        Ok(num_written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+impl<W: OutputStream> OutputStream for GziWriter<W> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        // the BGZF stream is complete once the compressor finished its last block
+        let mut writer = *self;
+        writer.write_index()?;
+        Box::new(writer.inner).finish()
+    }
+}
+
+/// Writes a `.fai` line for each single-line FASTA record written to the inner handle
+///
+/// Offsets refer to the uncompressed stream, so together with the `.gzi` of a BGZF output
+/// the records can be fetched with `samtools faidx`.
+pub struct FaiWriter<W: Write> {
+    inner: W,
+    index: BufWriter<File>,
+    /// Uncompressed bytes written so far
+    offset: u64,
+    /// Line of the current record (the sequence is on line 1)
+    line: u8,
+    /// Name of the current record (the header up to the first whitespace)
+    name: Vec<u8>,
+    name_complete: bool,
+    /// Offset and length of the current sequence
+    seq_offset: u64,
+    seq_len: u64,
+}
+impl<W: Write> FaiWriter<W> {
+    pub fn new(inner: W, index: File) -> Self {
+        Self {
+            inner,
+            index: BufWriter::new(index),
+            offset: 0,
+            line: 0,
+            name: Vec::new(),
+            name_complete: false,
+            seq_offset: 0,
+            seq_len: 0,
+        }
+    }
+
+    /// Follows the records across the written bytes
+    fn scan(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        for segment in buf.split_inclusive(|&b| b == b'\n') {
+            let complete = segment.last() == Some(&b'\n');
+            let content = &segment[..segment.len() - complete as usize];
+            if self.line == 0 {
+                if !self.name_complete {
+                    // skip the leading '>' of the header
+                    let content = match (self.name.is_empty(), content.first()) {
+                        (true, Some(b'>')) => &content[1..],
+                        _ => content,
+                    };
+                    match content.iter().position(|b| b.is_ascii_whitespace()) {
+                        Some(end) => {
+                            self.name.extend_from_slice(&content[..end]);
+                            self.name_complete = true;
+                        }
+                        None => self.name.extend_from_slice(content),
+                    }
+                }
+                if complete {
+                    self.line = 1;
+                    self.seq_offset = self.offset + segment.len() as u64;
+                    self.seq_len = 0;
+                }
+            } else {
+                self.seq_len += content.len() as u64;
+                if complete {
+                    self.index.write_all(&self.name)?;
+                    writeln!(
+                        self.index,
+                        "\t{len}\t{}\t{len}\t{}",
+                        self.seq_offset,
+                        self.seq_len + 1,
+                        len = self.seq_len,
+                    )?;
+                    self.line = 0;
+                    self.name.clear();
+                    self.name_complete = false;
+                }
+            }
+            self.offset += segment.len() as u64;
+        }
+        Ok(())
+    }
+}
+impl<W: Write> Write for FaiWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let num_written = self.inner.write(buf)?;
+        self.scan(&buf[..num_written])?;
+        Ok(num_written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()?;
+        self.index.flush()
+    }
+}
+impl<W: OutputStream> OutputStream for FaiWriter<W> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        let mut writer = *self;
+        writer.index.flush()?;
+        Box::new(writer.inner).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gzp::deflate::Bgzf;
+    use gzp::par::compress::{ParCompress, ParCompressBuilder};
+    use gzp::ZWriter;
+    use std::io::Read;
+    use tempfile::TempDir;
+
+    // GziWriter tests
+    #[test]
+    fn gzi_writer_indexes_bgzf_blocks() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("out.fa.bgz");
+        let path = path.to_str().unwrap();
+        // larger than a single BGZF block
+        let records = b">1\nACGTACGTAC\n".repeat(20000);
+
+        // compress the records up front to pass their blocks through the index
+        let compressed = temp_dir.path().join("raw.bgz");
+        let mut pt: ParCompress<Bgzf> = ParCompressBuilder::default()
+            .num_threads(2)
+            .unwrap()
+            .from_writer(File::create(&compressed).unwrap());
+        pt.write_all(&records).unwrap();
+        pt.finish().unwrap();
+
+        let mut writer = Box::new(GziWriter::new(
+            File::create(path).unwrap(),
+            File::create(gzi_path(path)).unwrap(),
+        ));
+        for chunk in std::fs::read(&compressed).unwrap().chunks(1000) {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut index = Vec::new();
+        File::open(gzi_path(path))
+            .unwrap()
+            .read_to_end(&mut index)
+            .unwrap();
+        let values: Vec<u64> = index
+            .chunks_exact(8)
+            .map(|v| u64::from_le_bytes(v.try_into().unwrap()))
+            .collect();
+        let num_entries = values[0] as usize;
+        assert!(num_entries > 1);
+        assert_eq!(values.len(), 1 + 2 * num_entries);
+
+        let entries: Vec<_> = values[1..].chunks_exact(2).collect();
+        assert!(entries
+            .windows(2)
+            .all(|w| w[0][0] < w[1][0] && w[0][1] < w[1][1]));
+        // the last data block ends with the uncompressed stream
+        let last = entries.last().unwrap();
+        assert_eq!(last[1], records.len() as u64);
+        assert!(last[0] <= std::fs::metadata(path).unwrap().len());
+    }
+
+    #[test]
+    fn gzi_writer_rejects_other_streams() {
+        let temp_dir = TempDir::new().unwrap();
+        let index = File::create(temp_dir.path().join("out.gzi")).unwrap();
+        let mut writer = GziWriter::new(Vec::new(), index);
+        assert!(writer.write_all(&[0; BGZF_HEADER_SIZE]).is_err());
+    }
+
+    // FaiWriter tests
+    #[test]
+    fn fai_writer_indexes_split_records() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("out.fa");
+        let path = path.to_str().unwrap();
+
+        let mut writer = FaiWriter::new(Vec::new(), File::create(fai_path(path)).unwrap());
+        // records are split across writes
+        writer.write_all(b">a 1").unwrap();
+        writer.write_all(b"\nAC").unwrap();
+        writer.write_all(b"GT\n>b\nAC\n").unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        let index = std::fs::read_to_string(fai_path(path)).unwrap();
+        assert_eq!(index, "a\t4\t5\t4\t5\nb\t2\t13\t2\t3\n");
+    }
+}
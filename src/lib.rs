@@ -8,7 +8,13 @@ pub const RECORD_CAPACITY: usize = 1024;
 pub mod cli;
 pub mod describe;
 pub mod dump;
+pub mod index;
+pub mod manifest;
 pub mod output;
+pub mod partial;
 pub mod prefetch;
+pub mod progress;
+pub mod quality;
 pub mod recode;
+pub mod spots;
 pub mod utils;
@@ -3,15 +3,22 @@ use anyhow::Result;
 mod cli;
 mod describe;
 mod dump;
+mod index;
+mod manifest;
 mod output;
+mod partial;
 mod prefetch;
+mod progress;
+mod quality;
 mod recode;
+mod spots;
 mod utils;
 
 use clap::Parser;
 use cli::Cli;
 use describe::describe;
 use dump::dump;
+use partial::PartialOutputs;
 use prefetch::prefetch;
 use recode::recode;
 
@@ -21,13 +28,23 @@ pub const RECORD_CAPACITY: usize = 1024;
 fn main() -> Result<()> {
     let args = Cli::parse();
     match args.command {
-        cli::Command::Dump(args) => dump(
-            &args.input,
-            args.runtime.threads(),
-            &args.output,
-            args.filter,
-        ),
-        cli::Command::Recode(args) => recode(&args),
+        cli::Command::Dump(args) => {
+            let outputs = PartialOutputs::default();
+            partial::install_interrupt_handler(&outputs)?;
+            dump(
+                &args.input,
+                &args.runtime,
+                &args.output,
+                args.filter,
+                &args.quality,
+                outputs,
+            )
+        }
+        cli::Command::Recode(args) => {
+            let outputs = PartialOutputs::default();
+            partial::install_interrupt_handler(&outputs)?;
+            recode(&args, outputs)
+        }
         cli::Command::Describe(args) => describe(&args.input, &args.options),
         cli::Command::Prefetch(args) => {
             // Only prefetch is fully async. Other commands
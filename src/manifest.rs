@@ -0,0 +1,252 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use anyhow::Result;
+use md5::{Digest, Md5};
+use serde::Serialize;
+use sha2::Sha256;
+
+use crate::output::OutputStream;
+
+/// Provenance record of all output files of a run
+#[derive(Debug, Serialize)]
+pub struct Manifest {
+    pub xsra_version: String,
+    pub accession: String,
+    /// URL (or local path) the records were read from
+    pub source: String,
+    pub outputs: Vec<OutputEntry>,
+}
+
+/// Provenance record of a single output file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OutputEntry {
+    pub path: String,
+    pub format: String,
+    /// Compression applied to the whole file (the column codec of parquet files)
+    pub compression: String,
+    pub reads: Option<u64>,
+    pub bases: Option<u64>,
+    pub bytes: u64,
+    pub md5: String,
+    pub sha256: String,
+}
+
+/// Counts and digests of an output file which is being written
+struct FileSummary {
+    format: String,
+    compression: String,
+    reads: Option<u64>,
+    bases: Option<u64>,
+    bytes: u64,
+    md5: Md5,
+    sha256: Sha256,
+}
+
+/// Counts and digests of an output file which are filled in while it is written
+///
+/// Clones share the same summary, so it can be collected once the writers are finished.
+#[derive(Clone)]
+pub struct OutputSummary {
+    path: String,
+    inner: Arc<Mutex<FileSummary>>,
+}
+impl OutputSummary {
+    pub fn new(path: &str, format: &str, compression: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            inner: Arc::new(Mutex::new(FileSummary {
+                format: format.to_string(),
+                compression: compression.to_string(),
+                reads: None,
+                bases: None,
+                bytes: 0,
+                md5: Md5::new(),
+                sha256: Sha256::new(),
+            })),
+        }
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn lock(&self) -> MutexGuard<'_, FileSummary> {
+        self.inner.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Sets the number of reads and bases of an output which cannot be counted while writing
+    pub fn set_counts(&self, reads: u64, bases: u64) {
+        let mut summary = self.lock();
+        summary.reads = Some(reads);
+        summary.bases = Some(bases);
+    }
+
+    /// Sets the compression of an output which is compressed by its format (e.g. parquet)
+    pub fn set_compression(&self, compression: &str) {
+        self.lock().compression = compression.to_string();
+    }
+
+    /// Collects the entry of the output
+    ///
+    /// All handles of the output must be finished beforehand.
+    pub fn entry(&self) -> OutputEntry {
+        let summary = self.lock();
+        OutputEntry {
+            path: self.path.clone(),
+            format: summary.format.clone(),
+            compression: summary.compression.clone(),
+            reads: summary.reads,
+            bases: summary.bases,
+            bytes: summary.bytes,
+            md5: format!("{:x}", summary.md5.clone().finalize()),
+            sha256: format!("{:x}", summary.sha256.clone().finalize()),
+        }
+    }
+}
+
+/// Wraps the (compressed) file handle of an output to hash the written bytes
+pub fn hash_file<W: Write>(inner: W, summary: &OutputSummary) -> HashingWriter<W> {
+    HashingWriter {
+        inner,
+        summary: summary.clone(),
+    }
+}
+
+/// Wraps the uncompressed stream of an output to count its FASTQ/FASTA records
+pub fn count_records<W: Write>(
+    inner: W,
+    summary: &OutputSummary,
+    lines_per_record: u64,
+) -> RecordCounter<W> {
+    summary.set_counts(0, 0);
+    RecordCounter {
+        inner,
+        summary: summary.clone(),
+        lines_per_record,
+        line: 0,
+        seq_len: 0,
+    }
+}
+
+impl Manifest {
+    pub fn new(accession: &str, source: &str, outputs: Vec<OutputEntry>) -> Self {
+        Self {
+            xsra_version: env!("CARGO_PKG_VERSION").to_string(),
+            accession: accession.to_string(),
+            source: source.to_string(),
+            outputs,
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Hashes all bytes written to the inner (file) handle
+pub struct HashingWriter<W: Write> {
+    inner: W,
+    summary: OutputSummary,
+}
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let num_written = self.inner.write(buf)?;
+        let mut summary = self.summary.lock();
+        summary.md5.update(&buf[..num_written]);
+        summary.sha256.update(&buf[..num_written]);
+        summary.bytes += num_written as u64;
+        Ok(num_written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+impl<W: OutputStream> OutputStream for HashingWriter<W> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        Box::new(self.inner).finish()
+    }
+}
+
+/// Counts the records and bases of single-line FASTQ/FASTA records written to the inner handle
+pub struct RecordCounter<W: Write> {
+    inner: W,
+    summary: OutputSummary,
+    /// 4 for FASTQ and 2 for FASTA
+    lines_per_record: u64,
+    /// Line of the current record (the sequence is on line 1)
+    line: u64,
+    /// Length of the current sequence line
+    seq_len: u64,
+}
+impl<W: Write> Write for RecordCounter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let num_written = self.inner.write(buf)?;
+        let mut reads = 0;
+        let mut bases = 0;
+        for segment in buf[..num_written].split_inclusive(|&b| b == b'\n') {
+            let complete = segment.last() == Some(&b'\n');
+            if self.line == 1 {
+                self.seq_len += segment.len() as u64 - complete as u64;
+            }
+            if complete {
+                if self.line == 1 {
+                    reads += 1;
+                    bases += self.seq_len;
+                    self.seq_len = 0;
+                }
+                self.line = (self.line + 1) % self.lines_per_record;
+            }
+        }
+        let mut summary = self.summary.lock();
+        summary.reads = summary.reads.map(|count| count + reads);
+        summary.bases = summary.bases.map(|count| count + bases);
+        Ok(num_written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+impl<W: OutputStream> OutputStream for RecordCounter<W> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        Box::new(self.inner).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // RecordCounter tests
+    #[test]
+    fn record_counter_counts_split_fastq_records() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("out.fq");
+        let path = path.to_str().unwrap();
+
+        let summary = OutputSummary::new(path, "fq", "none");
+        let hashing = hash_file(File::create(path).unwrap(), &summary);
+        let mut counter = count_records(hashing, &summary, 4);
+        // records are split across writes
+        counter.write_all(b"@1\nACG").unwrap();
+        counter.write_all(b"T\n+\nIIII\n@2\nAC\n+\nII\n").unwrap();
+        drop(counter);
+
+        let entry = summary.entry();
+        assert_eq!(entry.reads, Some(2));
+        assert_eq!(entry.bases, Some(6));
+        assert_eq!(entry.bytes, 26);
+        assert_eq!(
+            entry.md5,
+            format!("{:x}", Md5::digest(b"@1\nACGT\n+\nIIII\n@2\nAC\n+\nII\n"))
+        );
+    }
+}
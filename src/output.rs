@@ -1,21 +1,68 @@
 use std::fmt;
 use std::fs::File;
-use std::io::{stdout, BufWriter, Write};
+use std::io::{stdout, BufWriter, Seek, SeekFrom, Write};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
 
 use anyhow::{bail, Result};
+use bzip2::write::BzEncoder;
 use clap::ValueEnum;
 use gzp::deflate::{Bgzf, Gzip};
 use gzp::par::compress::{ParCompress, ParCompressBuilder};
+use gzp::{FormatSpec, ZWriter};
+use parking_lot::Mutex;
+use std::net::TcpStream;
 use std::process::Command;
-use zstd::Encoder;
+use xz2::stream::{Check, MtStreamBuilder};
+use xz2::write::XzEncoder;
 
 use crate::cli::FilterOptions;
 use crate::cli::OutputFormat;
+use crate::index::{self, FaiWriter, GziWriter};
+use crate::manifest;
+use crate::partial::PartialOutputs;
 #[cfg(target_family = "unix")]
 use std::os::unix::fs::FileTypeExt;
 
 use super::BUFFER_SIZE;
 
+/// An output handle which is finished explicitly
+///
+/// Compressed streams are only complete once their trailer was written, which can fail.
+/// Finishing them explicitly reports these errors instead of losing them on drop.
+pub trait OutputStream: Write + Send {
+    /// Completes the stream and flushes it to its destination
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// A shorthand for the type of output handles we expect to write
+pub type BoxedWriter = Box<dyn OutputStream>;
+
+impl OutputStream for BoxedWriter {
+    fn finish(self: Box<Self>) -> Result<()> {
+        (*self).finish()
+    }
+}
+
+// Uncompressed handles are complete once flushed
+impl<W: Write + Send> OutputStream for BufWriter<W> {
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.flush()?;
+        Ok(())
+    }
+}
+impl OutputStream for File {
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        self.flush()?;
+        Ok(())
+    }
+}
+impl OutputStream for std::io::Empty {
+    fn finish(self: Box<Self>) -> Result<()> {
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
 pub enum Compression {
     #[clap(name = "u")]
@@ -26,6 +73,12 @@ pub enum Compression {
     Bgzip,
     #[clap(name = "z")]
     Zstd,
+    #[clap(name = "x")]
+    Xz,
+    #[clap(name = "j")]
+    Bzip2,
+    #[clap(name = "l")]
+    Lz4,
 }
 impl Compression {
     pub fn ext(&self) -> Option<&str> {
@@ -34,6 +87,70 @@ impl Compression {
             Compression::Gzip => Some("gz"),
             Compression::Bgzip => Some("bgz"),
             Compression::Zstd => Some("zst"),
+            Compression::Xz => Some("xz"),
+            Compression::Bzip2 => Some("bz2"),
+            Compression::Lz4 => Some("lz4"),
+        }
+    }
+
+    /// Accepted compression levels of the codec (`None` if it has no levels)
+    pub fn levels(&self) -> Option<RangeInclusive<i32>> {
+        match self {
+            Compression::Uncompressed => None,
+            Compression::Gzip | Compression::Bgzip | Compression::Xz => Some(0..=9),
+            Compression::Zstd => Some(1..=22),
+            Compression::Bzip2 => Some(1..=9),
+            Compression::Lz4 => Some(0..=12),
+        }
+    }
+}
+
+/// A compression codec along with its level (the codec default if unset)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Codec {
+    pub compression: Compression,
+    pub level: Option<i32>,
+    /// Write a `.gzi` block index (and a `.fai` for FASTA) next to BGZF output files
+    pub index: bool,
+}
+impl Codec {
+    pub fn new(compression: Compression, level: Option<i32>) -> Result<Self> {
+        if let Some(level) = level {
+            match compression.levels() {
+                Some(levels) if levels.contains(&level) => {}
+                Some(levels) => bail!(
+                    "Compression level {level} is out of range for {compression:?} ({}-{})",
+                    levels.start(),
+                    levels.end()
+                ),
+                None => bail!("A compression level requires a compressed output"),
+            }
+        }
+        Ok(Self {
+            compression,
+            level,
+            index: false,
+        })
+    }
+
+    /// Requests random access indices of the output files (BGZF only)
+    pub fn with_index(self, index: bool) -> Result<Self> {
+        if index && self.compression != Compression::Bgzip {
+            bail!("Output indices require bgzip compression (-cb)");
+        }
+        Ok(Self { index, ..self })
+    }
+
+    pub fn ext(&self) -> Option<&str> {
+        self.compression.ext()
+    }
+}
+impl From<Compression> for Codec {
+    fn from(compression: Compression) -> Self {
+        Self {
+            compression,
+            level: None,
+            index: false,
         }
     }
 }
@@ -43,6 +160,12 @@ pub enum OutputFileType<'a> {
     RegularFile(&'a str),
     NamedPipe(&'a str),
     StdOut,
+    /// A file descriptor inherited from the parent process (e.g. bash process substitution)
+    FileDescriptor(i32),
+    /// A listening Unix domain socket
+    UnixSocket(&'a str),
+    /// A listening TCP endpoint (<host>:<port>)
+    Tcp(&'a str),
 }
 
 impl fmt::Display for OutputFileType<'_> {
@@ -50,17 +173,33 @@ impl fmt::Display for OutputFileType<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::StdOut => write!(f, "stdout"),
-            Self::RegularFile(fname) | Self::NamedPipe(fname) => f.write_str(fname),
+            Self::RegularFile(fname) | Self::NamedPipe(fname) | Self::UnixSocket(fname) => {
+                f.write_str(fname)
+            }
+            Self::FileDescriptor(fd) => write!(f, "fd:{fd}"),
+            Self::Tcp(addr) => write!(f, "tcp://{addr}"),
         }
     }
 }
 
-impl OutputFileType<'_> {
+impl<'a> OutputFileType<'a> {
+    /// A named pipe or a regular file at the given path
+    pub fn file(path: &'a str, is_fifo: bool) -> Self {
+        if is_fifo {
+            Self::NamedPipe(path)
+        } else {
+            Self::RegularFile(path)
+        }
+    }
+
     fn sep(&self) -> &str {
         match self {
             OutputFileType::RegularFile(_) => "/",
             OutputFileType::NamedPipe(_) => ".",
-            OutputFileType::StdOut => unreachable!("should not happen"),
+            OutputFileType::StdOut
+            | OutputFileType::FileDescriptor(_)
+            | OutputFileType::UnixSocket(_)
+            | OutputFileType::Tcp(_) => unreachable!("should not happen"),
         }
     }
 }
@@ -113,10 +252,11 @@ fn create_fifo_if_absent(path: OutputFileType) -> Result<()> {
     Ok(())
 }
 
-fn writer_from_path(path: OutputFileType) -> Result<Box<dyn Write + Send>> {
+fn writer_from_path(path: OutputFileType, outputs: &PartialOutputs) -> Result<BoxedWriter> {
     match path {
         OutputFileType::RegularFile(path) => {
-            let file = File::create(path)?;
+            // regular files are renamed to their final name once the run succeeded
+            let file = outputs.create(path)?;
             let writer = BufWriter::with_capacity(BUFFER_SIZE, file);
             Ok(Box::new(writer))
         }
@@ -128,42 +268,233 @@ fn writer_from_path(path: OutputFileType) -> Result<Box<dyn Write + Send>> {
             let file = std::fs::OpenOptions::new().write(true).open(path)?;
             Ok(Box::new(file))
         }
+        OutputFileType::FileDescriptor(fd) => file_from_fd(fd),
+        OutputFileType::UnixSocket(path) => unix_socket_writer(path),
+        OutputFileType::Tcp(addr) => match TcpStream::connect(addr) {
+            Ok(stream) => Ok(Box::new(BufWriter::with_capacity(BUFFER_SIZE, stream))),
+            Err(err) => bail!("Failed to connect to the TCP endpoint {addr}: {err}"),
+        },
     }
 }
 
-fn compression_passthrough<W: Write + Send + 'static>(
-    writer: W,
-    compression: Compression,
+#[cfg(target_family = "unix")]
+fn file_from_fd(fd: i32) -> Result<BoxedWriter> {
+    use std::os::fd::FromRawFd;
+
+    // only take ownership of descriptors which were actually inherited
+    if unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        bail!("File descriptor {fd} is not open");
+    }
+    // the standard streams stay open for the rest of the process, so only a duplicate is closed
+    let fd = if fd <= 2 {
+        match unsafe { libc::dup(fd) } {
+            -1 => bail!(
+                "Failed to duplicate file descriptor {fd}: {}",
+                std::io::Error::last_os_error()
+            ),
+            dup => dup,
+        }
+    } else {
+        fd
+    };
+    let file = unsafe { std::fs::File::from_raw_fd(fd) };
+    Ok(Box::new(file))
+}
+
+#[cfg(not(target_family = "unix"))]
+fn file_from_fd(_fd: i32) -> Result<BoxedWriter> {
+    bail!("File descriptor outputs are not supported on non-unix (i.e. non linux/MacOS) systems.");
+}
+
+#[cfg(target_family = "unix")]
+fn unix_socket_writer(path: &str) -> Result<BoxedWriter> {
+    match std::os::unix::net::UnixStream::connect(path) {
+        Ok(stream) => Ok(Box::new(BufWriter::with_capacity(BUFFER_SIZE, stream))),
+        Err(err) => bail!("Failed to connect to the Unix socket {path}: {err}"),
+    }
+}
+
+#[cfg(not(target_family = "unix"))]
+fn unix_socket_writer(_path: &str) -> Result<BoxedWriter> {
+    bail!("Unix socket outputs are not supported on non-unix (i.e. non linux/MacOS) systems.");
+}
+
+/// Compresses the stream written to `writer` with the codec
+///
+/// BGZF outputs also get their block index written to `gzi` if the codec requests one.
+fn compression_passthrough(
+    writer: BoxedWriter,
+    codec: Codec,
     num_threads: usize,
-) -> Result<Box<dyn Write + Send>> {
-    match compression {
-        Compression::Uncompressed => Ok(Box::new(writer)),
+    gzi: Option<File>,
+) -> Result<BoxedWriter> {
+    match codec.compression {
+        Compression::Uncompressed => Ok(writer),
         Compression::Gzip => {
-            let pt: ParCompress<Gzip> = ParCompressBuilder::default()
-                .num_threads(num_threads)?
-                .from_writer(writer);
-            Ok(Box::new(pt))
+            let mut builder = ParCompressBuilder::default().num_threads(num_threads)?;
+            if let Some(level) = codec.level {
+                builder = builder.compression_level(gzp::Compression::new(level as u32));
+            }
+            Ok(Box::new(ParOutput::<Gzip>::new(builder, writer)))
         }
         Compression::Bgzip => {
-            let pt: ParCompress<Bgzf> = ParCompressBuilder::default()
-                .num_threads(num_threads)?
-                .from_writer(writer);
-            Ok(Box::new(pt))
+            let mut builder = ParCompressBuilder::default().num_threads(num_threads)?;
+            if let Some(level) = codec.level {
+                builder = builder.compression_level(gzp::Compression::new(level as u32));
+            }
+            let writer: BoxedWriter = match gzi.filter(|_| codec.index) {
+                Some(gzi) => Box::new(GziWriter::new(writer, gzi)),
+                None => writer,
+            };
+            Ok(Box::new(ParOutput::<Bgzf>::new(builder, writer)))
         }
         Compression::Zstd => {
-            let mut pt = Encoder::new(writer, 3)?;
+            let mut pt = zstd::Encoder::new(writer, codec.level.unwrap_or(3))?;
             pt.multithread(num_threads as u32)?;
-            Ok(Box::new(pt.auto_finish()))
+            Ok(Box::new(pt))
         }
+        Compression::Xz => {
+            let stream = MtStreamBuilder::new()
+                .threads(num_threads.max(1) as u32)
+                .preset(codec.level.unwrap_or(6) as u32)
+                .check(Check::Crc64)
+                .encoder()?;
+            Ok(Box::new(XzEncoder::new_stream(writer, stream)))
+        }
+        Compression::Bzip2 => {
+            // bzip2 streams are compressed on a single thread
+            let level = bzip2::Compression::new(codec.level.unwrap_or(6) as u32);
+            Ok(Box::new(BzEncoder::new(writer, level)))
+        }
+        Compression::Lz4 => {
+            // lz4 frames are compressed on a single thread
+            let pt = lz4::EncoderBuilder::new()
+                .level(codec.level.unwrap_or(0) as u32)
+                .build(writer)?;
+            Ok(Box::new(pt))
+        }
+    }
+}
+
+/// A gzp compressor which finishes the stream it writes to once it is done
+///
+/// gzp moves its writer to a compression thread (and drops it there),
+/// so the stream is shared with that thread to finish it afterwards.
+struct ParOutput<F: FormatSpec> {
+    pt: ParCompress<F>,
+    inner: Arc<Mutex<BoxedWriter>>,
+}
+impl<F: FormatSpec> ParOutput<F> {
+    fn new(builder: ParCompressBuilder<F>, writer: BoxedWriter) -> Self {
+        let inner = Arc::new(Mutex::new(writer));
+        Self {
+            pt: builder.from_writer(SharedWriter(inner.clone())),
+            inner,
+        }
+    }
+}
+impl<F: FormatSpec> Write for ParOutput<F> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pt.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.pt.flush()
+    }
+}
+impl<F: FormatSpec> OutputStream for ParOutput<F> {
+    fn finish(mut self: Box<Self>) -> Result<()> {
+        // joins the compression threads, which releases their handle of the stream
+        self.pt.finish()?;
+        let Ok(inner) = Arc::try_unwrap(self.inner) else {
+            bail!("Compressed stream is still held by a compression thread");
+        };
+        inner.into_inner().finish()
+    }
+}
+
+/// The writer handed to a gzp compressor
+struct SharedWriter(Arc<Mutex<BoxedWriter>>);
+impl Write for SharedWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.lock().flush()
+    }
+}
+
+impl OutputStream for zstd::Encoder<'static, BoxedWriter> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        zstd::Encoder::finish(*self)?.finish()
+    }
+}
+impl OutputStream for XzEncoder<BoxedWriter> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        XzEncoder::finish(*self)?.finish()
+    }
+}
+impl OutputStream for BzEncoder<BoxedWriter> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        BzEncoder::finish(*self)?.finish()
+    }
+}
+impl OutputStream for lz4::Encoder<BoxedWriter> {
+    fn finish(self: Box<Self>) -> Result<()> {
+        let (writer, result) = lz4::Encoder::finish(*self);
+        result?;
+        writer.finish()
     }
 }
 
+/// Builds the (compressed) writer of an output file
+///
+/// Regular files are hashed and their records counted if a manifest was requested,
+/// and indexed if the codec requests it.
+fn build_file_writer(
+    path: OutputFileType,
+    codec: Codec,
+    format: OutputFormat,
+    num_threads: usize,
+    outputs: &PartialOutputs,
+) -> Result<BoxedWriter> {
+    let writer = writer_from_path(path, outputs)?;
+    let OutputFileType::RegularFile(path) = path else {
+        return compression_passthrough(writer, codec, num_threads, None);
+    };
+    let summary = outputs.summary(path, format.ext(), codec.ext().unwrap_or("none"));
+    let writer: BoxedWriter = match &summary {
+        Some(summary) => Box::new(manifest::hash_file(writer, summary)),
+        None => writer,
+    };
+    let gzi = if codec.index && codec.compression == Compression::Bgzip {
+        Some(outputs.create(&index::gzi_path(path))?)
+    } else {
+        None
+    };
+    let mut writer = compression_passthrough(writer, codec, num_threads, gzi)?;
+    if let Some(summary) = &summary {
+        // only single-line FASTQ/FASTA records can be counted from the written stream
+        writer = match format {
+            OutputFormat::Fastq => Box::new(manifest::count_records(writer, summary, 4)),
+            OutputFormat::Fasta => Box::new(manifest::count_records(writer, summary, 2)),
+            _ => writer,
+        };
+    }
+    if codec.index && format == OutputFormat::Fasta {
+        let fai = outputs.create(&index::fai_path(path))?;
+        writer = Box::new(FaiWriter::new(writer, fai));
+    }
+    Ok(writer)
+}
+
 pub fn build_path_name(
     outdir: OutputFileType,
     prefix: &str,
     compression: Compression,
     format: OutputFormat,
-    seg_id: usize,
+    seg_id: impl fmt::Display,
 ) -> String {
     let out_sep = outdir.sep();
     let format_ext = format.ext();
@@ -174,68 +505,255 @@ pub fn build_path_name(
     }
 }
 
+/// Builds the path of one part of a chunked output: <outdir>/<prefix><seg_id>.part<NNNN>.<ext>
+pub fn build_part_path_name(
+    outdir: &str,
+    prefix: &str,
+    compression: Compression,
+    format: OutputFormat,
+    seg_id: impl fmt::Display,
+    part: usize,
+) -> String {
+    build_path_name(
+        OutputFileType::RegularFile(outdir),
+        prefix,
+        compression,
+        format,
+        format!("{seg_id}.part{part:04}"),
+    )
+}
+
+/// Thresholds at which chunked outputs start a new part
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkLimits {
+    /// Maximum number of reads per file
+    pub reads: Option<u64>,
+    /// Maximum number of (uncompressed) bytes per file
+    pub bytes: Option<u64>,
+}
+impl ChunkLimits {
+    /// Returns `None` if no threshold was provided
+    pub fn new(reads: Option<u64>, bytes: Option<u64>) -> Option<Self> {
+        (reads.is_some() || bytes.is_some()).then_some(Self { reads, bytes })
+    }
+
+    /// Whether appending reads to a non-empty part would cross a threshold
+    pub fn exceeded_by(&self, part_reads: u64, part_bytes: u64, reads: u64, bytes: u64) -> bool {
+        if part_reads == 0 && part_bytes == 0 {
+            return false;
+        }
+        self.reads.is_some_and(|max| part_reads + reads > max)
+            || self.bytes.is_some_and(|max| part_bytes + bytes > max)
+    }
+}
+
+/// Builds the writer of one part of a chunked output file
+#[allow(clippy::too_many_arguments)]
+pub fn build_part_writer(
+    outdir: &str,
+    prefix: &str,
+    codec: Codec,
+    format: OutputFormat,
+    num_threads: usize,
+    name: &str,
+    part: usize,
+    outputs: &PartialOutputs,
+) -> Result<BoxedWriter> {
+    std::fs::create_dir_all(outdir)?;
+    let path = build_part_path_name(outdir, prefix, codec.compression, format, name, part);
+    build_file_writer(
+        OutputFileType::RegularFile(&path),
+        codec,
+        format,
+        compression_threads(num_threads),
+        outputs,
+    )
+}
+
+/// Reopens an output file to append further compressed members (or plain records)
+///
+/// The file is first truncated to `size` bytes, discarding anything written after that point.
+pub fn build_append_writer(
+    path: &str,
+    size: u64,
+    codec: Codec,
+    num_threads: usize,
+) -> Result<BoxedWriter> {
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path)?;
+    file.set_len(size)?;
+    let mut writer = BufWriter::with_capacity(BUFFER_SIZE, file);
+    writer.seek(SeekFrom::End(0))?;
+    compression_passthrough(
+        Box::new(writer),
+        codec,
+        compression_threads(num_threads),
+        None,
+    )
+}
+
+/// Number of compression threads given to each output file
+///
+/// If four or more threads were allocated to `xsra`, use that number divided by four for
+/// compression. If fewer than four total threads were allocated, just set aside one thread.
+fn compression_threads(num_threads: usize) -> usize {
+    (num_threads / 4).max(1)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn build_writers(
     outdir: Option<&str>,
     prefix: &str,
-    compression: Compression,
+    codec: Codec,
     format: OutputFormat,
     num_threads: usize,
     filter_opts: &FilterOptions,
     is_fifo: bool,
-) -> Result<Vec<Box<dyn Write + Send>>> {
+    outputs: &PartialOutputs,
+) -> Result<Vec<BoxedWriter>> {
     if let Some(outdir) = outdir {
-        // create directory if it doesn't exist
-        if !std::path::Path::new(outdir).exists() && !is_fifo {
-            std::fs::create_dir(outdir)?;
+        // only create actual writers if we won't filter out this segment anyway
+        let names: Vec<Option<String>> = (0..4)
+            .map(|i| {
+                (filter_opts.include.is_empty() || filter_opts.include.contains(&i))
+                    .then(|| i.to_string())
+            })
+            .collect();
+        build_named_writers(
+            outdir,
+            prefix,
+            codec,
+            format,
+            num_threads,
+            &names,
+            is_fifo,
+            outputs,
+        )
+    } else {
+        let mut writers = vec![];
+        let writer = writer_from_path(OutputFileType::StdOut, outputs)?;
+        let writer = compression_passthrough(writer, codec, num_threads, None)?;
+        writers.push(writer);
+        Ok(writers)
+    }
+}
+
+/// Builds one writer per name following the pattern: <outdir>/<prefix><name>.<ext>
+///
+/// Names which are `None` are given an empty writer.
+#[allow(clippy::too_many_arguments)]
+pub fn build_named_writers(
+    outdir: &str,
+    prefix: &str,
+    codec: Codec,
+    format: OutputFormat,
+    num_threads: usize,
+    names: &[Option<String>],
+    is_fifo: bool,
+    outputs: &PartialOutputs,
+) -> Result<Vec<BoxedWriter>> {
+    // create directory if it doesn't exist
+    if !std::path::Path::new(outdir).exists() && !is_fifo {
+        std::fs::create_dir(outdir)?;
+    }
+
+    let c_threads = compression_threads(num_threads);
+    let mut writers = vec![];
+    if is_fifo {
+        for name in names.iter().flatten() {
+            let path = build_path_name(
+                OutputFileType::file(outdir, is_fifo),
+                prefix,
+                codec.compression,
+                format,
+                name,
+            );
+            create_fifo_if_absent(OutputFileType::NamedPipe(&path))?;
         }
+    }
 
-        // If four or more threads were allocated to `xsra`, use that number divided by four for
-        // compression. If fewer than four total threads were allocated, just set aside one thread.
-        let c_threads = (num_threads / 4).max(1);
-        let mut writers = vec![];
-        if is_fifo {
-            for i in 0..4 {
-                if filter_opts.include.is_empty() || filter_opts.include.contains(&i) {
-                    let path = build_path_name(
-                        OutputFileType::NamedPipe(outdir),
-                        prefix,
-                        compression,
-                        format,
-                        i,
-                    );
-                    create_fifo_if_absent(OutputFileType::NamedPipe(&path))?;
-                }
-            }
+    for name in names {
+        if let Some(name) = name {
+            let path = build_path_name(
+                OutputFileType::file(outdir, is_fifo),
+                prefix,
+                codec.compression,
+                format,
+                name,
+            );
+            let writer = build_file_writer(
+                OutputFileType::file(&path, is_fifo),
+                codec,
+                format,
+                c_threads,
+                outputs,
+            )?;
+            writers.push(writer);
+        } else {
+            // otherwise, use the empty writer
+            let empty_writer = Box::new(std::io::empty());
+            writers.push(empty_writer);
         }
+    }
+    Ok(writers)
+}
 
-        for i in 0..4 {
-            let outf = |x| {
-                if is_fifo {
-                    OutputFileType::NamedPipe(x)
-                } else {
-                    OutputFileType::RegularFile(x)
-                }
-            };
-            // only create actual writers if we won't filter out this segment anyway
-            if filter_opts.include.is_empty() || filter_opts.include.contains(&i) {
-                let path = build_path_name(outf(outdir), prefix, compression, format, i);
-                let writer = writer_from_path(outf(&path))?;
-                let writer = compression_passthrough(writer, compression, c_threads)?;
-                writers.push(writer);
-            } else {
-                // otherwise, use the empty writer
-                let empty_writer = Box::new(std::io::empty());
-                writers.push(empty_writer);
+/// Builds one writer per named output which streams it to the next sink in order
+///
+/// Without any names (interleaved output), all records are streamed to a single sink.
+/// Names which are `None` are given an empty writer.
+pub fn build_sink_writers(
+    sinks: &[OutputFileType],
+    names: &[Option<String>],
+    codec: Codec,
+    format: OutputFormat,
+    num_threads: usize,
+    outputs: &PartialOutputs,
+) -> Result<Vec<BoxedWriter>> {
+    if names.is_empty() {
+        let [sink] = sinks else {
+            bail!(
+                "Interleaved output is streamed to a single sink, but {} were provided",
+                sinks.len()
+            );
+        };
+        return Ok(vec![build_file_writer(
+            *sink,
+            codec,
+            format,
+            num_threads,
+            outputs,
+        )?]);
+    }
+
+    let num_outputs = names.iter().flatten().count();
+    if sinks.len() != num_outputs {
+        bail!(
+            "Each of the {num_outputs} outputs requires its own sink, but {} were provided",
+            sinks.len()
+        );
+    }
+    for (i, sink) in sinks.iter().enumerate() {
+        if sinks[..i].contains(sink) {
+            bail!("The sink {sink} was provided for multiple outputs");
+        }
+    }
+
+    let c_threads = compression_threads(num_threads);
+    let mut sinks = sinks.iter().peekable();
+    let mut writers = vec![];
+    for name in names {
+        match (name, sinks.next_if(|_| name.is_some())) {
+            (Some(_), Some(sink)) => {
+                writers.push(build_file_writer(*sink, codec, format, c_threads, outputs)?);
             }
+            _ => writers.push(Box::new(std::io::empty())),
         }
-        Ok(writers)
-    } else {
-        let mut writers = vec![];
-        let writer = writer_from_path(OutputFileType::StdOut)?;
-        let writer = compression_passthrough(writer, compression, num_threads)?;
-        writers.push(writer);
-        Ok(writers)
     }
+    Ok(writers)
 }
 
 #[cfg(test)]
@@ -319,8 +837,8 @@ mod tests {
         // File doesn't exist, should try to create fifo
         let result = create_fifo_if_absent(OutputFileType::NamedPipe(file_path.to_str().unwrap()));
 
-        if result.is_err() {
-            assert!(result.unwrap_err().to_string().contains("mkfifo"));
+        if let Err(err) = result {
+            assert!(err.to_string().contains("mkfifo"));
         }
     }
 
@@ -349,20 +867,19 @@ mod tests {
 
         // Test with specific segments included
         let filter_opts = FilterOptions {
-            min_read_len: 1,
-            skip_technical: false,
-            limit: None,
             include: vec![0, 2],
+            ..Default::default()
         };
 
         let result = build_writers(
             Some(new_dir.to_str().unwrap()),
             "test",
-            Compression::Uncompressed,
+            Compression::Uncompressed.into(),
             OutputFormat::Fasta,
             4,
             &filter_opts,
             false,
+            &PartialOutputs::default(),
         );
 
         assert!(result.is_ok());
@@ -381,20 +898,19 @@ mod tests {
 
         // Test with only segment 0 included, others should use empty writers
         let filter_opts = FilterOptions {
-            min_read_len: 1,
-            skip_technical: false,
-            limit: None,
             include: vec![0],
+            ..Default::default()
         };
 
         let result = build_writers(
             Some(temp_dir.path().to_str().unwrap()),
             "test",
-            Compression::Uncompressed,
+            Compression::Uncompressed.into(),
             OutputFormat::Fasta,
             4,
             &filter_opts,
             false,
+            &PartialOutputs::default(),
         );
 
         assert!(result.is_ok());
@@ -407,26 +923,191 @@ mod tests {
     fn build_writers_stdout_when_no_outdir() {
         use crate::cli::{FilterOptions, OutputFormat};
 
-        let filter_opts = FilterOptions {
-            min_read_len: 1,
-            skip_technical: false,
-            limit: None,
-            include: vec![],
-        };
+        let filter_opts = FilterOptions::default();
 
         // Tests stdout writer
         let result = build_writers(
             None,
             "test",
-            Compression::Uncompressed,
+            Compression::Uncompressed.into(),
             OutputFormat::Fasta,
             4,
             &filter_opts,
             false,
+            &PartialOutputs::default(),
         );
 
         assert!(result.is_ok());
         let writers = result.unwrap();
         assert_eq!(writers.len(), 1);
     }
+
+    // build_part_path_name tests
+    #[test]
+    fn build_part_path_name_pads_part_index() {
+        let path =
+            build_part_path_name("out", "seg_", Compression::Gzip, OutputFormat::Fastq, 1, 1);
+        assert_eq!(path, "out/seg_1.part0001.fq.gz");
+    }
+
+    // build_path_name tests
+    #[test]
+    fn build_path_name_uses_codec_extension() {
+        let outdir = OutputFileType::RegularFile("out");
+        for (compression, expected) in [
+            (Compression::Xz, "out/seg_0.fq.xz"),
+            (Compression::Bzip2, "out/seg_0.fq.bz2"),
+            (Compression::Lz4, "out/seg_0.fq.lz4"),
+        ] {
+            let path = build_path_name(outdir, "seg_", compression, OutputFormat::Fastq, 0);
+            assert_eq!(path, expected);
+        }
+    }
+
+    // Codec tests
+    #[test]
+    fn codec_validates_level() {
+        assert!(Codec::new(Compression::Zstd, Some(19)).is_ok());
+        assert!(Codec::new(Compression::Zstd, Some(23)).is_err());
+        assert!(Codec::new(Compression::Bzip2, Some(0)).is_err());
+        assert!(Codec::new(Compression::Lz4, Some(12)).is_ok());
+        assert!(Codec::new(Compression::Uncompressed, Some(1)).is_err());
+        assert!(Codec::new(Compression::Uncompressed, None).is_ok());
+    }
+
+    // compression_passthrough tests
+    #[test]
+    fn compression_passthrough_roundtrip() {
+        use std::io::Read;
+
+        let temp_dir = TempDir::new().unwrap();
+        let records = b"@1\nACGT\n+\nIIII\n".repeat(1000);
+        for compression in [
+            Compression::Zstd,
+            Compression::Xz,
+            Compression::Bzip2,
+            Compression::Lz4,
+        ] {
+            let path = temp_dir.path().join(compression.ext().unwrap());
+            let codec = Codec::new(compression, Some(1)).unwrap();
+            let file = Box::new(File::create(&path).unwrap());
+            let mut writer = compression_passthrough(file, codec, 2, None).unwrap();
+            writer.write_all(&records).unwrap();
+            writer.finish().unwrap();
+
+            let file = File::open(&path).unwrap();
+            let mut reader: Box<dyn Read> = match compression {
+                Compression::Zstd => Box::new(zstd::Decoder::new(file).unwrap()),
+                Compression::Xz => Box::new(xz2::read::XzDecoder::new(file)),
+                Compression::Bzip2 => Box::new(bzip2::read::BzDecoder::new(file)),
+                _ => Box::new(lz4::Decoder::new(file).unwrap()),
+            };
+            let mut decoded = Vec::new();
+            reader.read_to_end(&mut decoded).unwrap();
+            assert_eq!(decoded, records, "{compression:?} roundtrip");
+        }
+    }
+
+    // build_sink_writers tests
+    #[test]
+    fn build_sink_writers_requires_one_sink_per_output() {
+        let names = vec![Some("0".to_string()), None, Some("2".to_string())];
+        let sinks = [OutputFileType::Tcp("127.0.0.1:1")];
+        let result = build_sink_writers(
+            &sinks,
+            &names,
+            Compression::Uncompressed.into(),
+            OutputFormat::Fastq,
+            1,
+            &PartialOutputs::default(),
+        );
+        assert!(result
+            .err()
+            .unwrap()
+            .to_string()
+            .contains("Each of the 2 outputs requires its own sink"));
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn build_sink_writers_streams_to_fd_and_unix_socket() {
+        use std::io::Read;
+        use std::os::fd::IntoRawFd;
+        use std::os::unix::net::UnixListener;
+
+        let temp_dir = TempDir::new().unwrap();
+        let fd_path = temp_dir.path().join("fd.fq");
+        let fd = File::create(&fd_path).unwrap().into_raw_fd();
+        let socket_path = temp_dir.path().join("out.sock");
+        let listener = UnixListener::bind(&socket_path).unwrap();
+        let reader = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut received = Vec::new();
+            stream.read_to_end(&mut received).unwrap();
+            received
+        });
+
+        let names = vec![Some("0".to_string()), None, Some("2".to_string())];
+        let sinks = [
+            OutputFileType::FileDescriptor(fd),
+            OutputFileType::UnixSocket(socket_path.to_str().unwrap()),
+        ];
+        let mut writers = build_sink_writers(
+            &sinks,
+            &names,
+            Compression::Uncompressed.into(),
+            OutputFormat::Fastq,
+            1,
+            &PartialOutputs::default(),
+        )
+        .unwrap();
+        assert_eq!(writers.len(), 3);
+        writers[0].write_all(b"@1\nA\n+\nI\n").unwrap();
+        writers[2].write_all(b"@1\nC\n+\nI\n").unwrap();
+        for writer in writers {
+            writer.finish().unwrap();
+        }
+
+        assert_eq!(std::fs::read(&fd_path).unwrap(), b"@1\nA\n+\nI\n");
+        assert_eq!(reader.join().unwrap(), b"@1\nC\n+\nI\n");
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn writer_from_path_keeps_standard_streams_open() {
+        let writer = writer_from_path(
+            OutputFileType::FileDescriptor(2),
+            &PartialOutputs::default(),
+        )
+        .unwrap();
+        writer.finish().unwrap();
+        assert_ne!(unsafe { libc::fcntl(2, libc::F_GETFD) }, -1);
+    }
+
+    #[test]
+    #[cfg(target_family = "unix")]
+    fn writer_from_path_rejects_closed_fd() {
+        let result = writer_from_path(
+            OutputFileType::FileDescriptor(-1),
+            &PartialOutputs::default(),
+        );
+        assert!(result.is_err());
+    }
+
+    // ChunkLimits tests
+    #[test]
+    fn chunk_limits_requires_a_threshold() {
+        assert!(ChunkLimits::new(None, None).is_none());
+        assert!(ChunkLimits::new(Some(10), None).is_some());
+    }
+
+    #[test]
+    fn chunk_limits_exceeded_by_batch() {
+        let limits = ChunkLimits::new(Some(10), Some(1000)).unwrap();
+        // empty parts always accept the batch
+        assert!(!limits.exceeded_by(0, 0, 50, 5000));
+        assert!(!limits.exceeded_by(5, 100, 5, 100));
+        assert!(limits.exceeded_by(5, 100, 6, 100));
+        assert!(limits.exceeded_by(5, 900, 1, 101));
+    }
 }
@@ -0,0 +1,251 @@
+use std::fs::File;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+
+use anyhow::Result;
+
+use crate::manifest::OutputSummary;
+
+/// Suffix of incomplete output files
+const PARTIAL_SUFFIX: &str = ".partial";
+
+struct PartialFile {
+    /// Final path of the output
+    path: String,
+    /// Resumable outputs are kept on failure
+    resumable: bool,
+    /// Counts and digests of the output (if it is summarized for a manifest)
+    summary: Option<OutputSummary>,
+}
+impl PartialFile {
+    fn partial_path(&self) -> String {
+        partial_path(&self.path)
+    }
+}
+
+/// Temporary path of an output until it is complete
+pub fn partial_path(path: &str) -> String {
+    format!("{path}{PARTIAL_SUFFIX}")
+}
+
+/// Outputs which are currently written under their temporary name
+#[derive(Default)]
+struct Registry {
+    files: Vec<PartialFile>,
+    /// Whether the created outputs are summarized for a manifest
+    summarize: bool,
+}
+
+#[derive(Default)]
+struct PartialFiles(Mutex<Registry>);
+impl PartialFiles {
+    fn lock(&self) -> MutexGuard<'_, Registry> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn remove_all(&self) {
+        self.lock().files.iter().for_each(remove);
+    }
+}
+impl Drop for PartialFiles {
+    fn drop(&mut self) {
+        self.remove_all();
+    }
+}
+
+/// The output files of a run
+///
+/// Outputs are only renamed once they are complete, so that failed or interrupted runs
+/// never leave files behind which look valid. Clones share the same outputs: unless they
+/// are committed, the outputs are removed once the last handle is dropped (resumable
+/// outputs are kept under their temporary name).
+#[derive(Clone, Default)]
+pub struct PartialOutputs {
+    files: Arc<PartialFiles>,
+}
+impl PartialOutputs {
+    fn register(&self, path: &str, resumable: bool) {
+        let files = &mut self.files.lock().files;
+        if !files.iter().any(|file| file.path == path) {
+            files.push(PartialFile {
+                path: path.to_string(),
+                resumable,
+                summary: None,
+            });
+        }
+    }
+
+    /// Summarizes the outputs created from now on for a manifest
+    pub fn summarize(&self) {
+        self.files.lock().summarize = true;
+    }
+
+    /// Starts the summary of an output created with [`Self::create`]
+    ///
+    /// Returns `None` unless the outputs are summarized.
+    pub fn summary(&self, path: &str, format: &str, compression: &str) -> Option<OutputSummary> {
+        let mut registry = self.files.lock();
+        if !registry.summarize {
+            return None;
+        }
+        let file = registry.files.iter_mut().find(|file| file.path == path)?;
+        let summary = OutputSummary::new(path, format, compression);
+        file.summary = Some(summary.clone());
+        Some(summary)
+    }
+
+    /// Creates an output file under its temporary name
+    pub fn create(&self, path: &str) -> Result<File> {
+        self.register(path, false);
+        Ok(File::create(partial_path(path))?)
+    }
+
+    /// Registers an output which is kept under its temporary name on failure and returns that name
+    ///
+    /// A complete output of a previous run is moved back to its temporary name to be continued.
+    pub fn create_resumable(&self, path: &str) -> Result<String> {
+        self.register(path, true);
+        let partial = partial_path(path);
+        if !Path::new(&partial).exists() && Path::new(path).exists() {
+            std::fs::rename(path, &partial)?;
+        }
+        Ok(partial)
+    }
+
+    /// Renames the complete outputs to their final names and returns their summaries
+    ///
+    /// All handles of the outputs must be finished beforehand.
+    pub fn commit(self) -> Result<Vec<OutputSummary>> {
+        let files = std::mem::take(&mut self.files.lock().files);
+        let mut summaries = Vec::new();
+        for file in files {
+            let partial = file.partial_path();
+            if Path::new(&partial).exists() {
+                std::fs::rename(partial, &file.path)?;
+            }
+            summaries.extend(file.summary);
+        }
+        Ok(summaries)
+    }
+}
+
+fn remove(file: &PartialFile) {
+    if !file.resumable {
+        let _ = std::fs::remove_file(file.partial_path());
+    }
+}
+
+/// Removes the incomplete outputs and exits once the process receives SIGINT
+///
+/// Must be called before any other thread is spawned, as threads inherit the blocked signal.
+#[cfg(target_family = "unix")]
+pub fn install_interrupt_handler(outputs: &PartialOutputs) -> Result<()> {
+    // SIGINT is blocked for all threads and handled synchronously on a dedicated thread,
+    // which avoids doing any IO within a signal handler.
+    let set = unsafe {
+        let mut set = std::mem::zeroed::<libc::sigset_t>();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, libc::SIGINT);
+        let status = libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut());
+        if status != 0 {
+            anyhow::bail!("Failed to block SIGINT (error code {status})");
+        }
+        set
+    };
+    // the handler must not keep the outputs alive
+    let files: Weak<PartialFiles> = Arc::downgrade(&outputs.files);
+    std::thread::spawn(move || {
+        let mut signal = 0;
+        if unsafe { libc::sigwait(&set, &mut signal) } == 0 {
+            eprintln!("Interrupted: removing incomplete outputs");
+            if let Some(files) = files.upgrade() {
+                files.remove_all();
+            }
+            std::process::exit(130);
+        }
+    });
+    Ok(())
+}
+
+/// Interrupts are left to the default handler on non-unix systems
+#[cfg(not(target_family = "unix"))]
+pub fn install_interrupt_handler(_outputs: &PartialOutputs) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    // PartialOutputs tests
+    #[test]
+    fn partial_outputs_commit_renames_outputs() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("out.fq");
+        let path = path.to_str().unwrap();
+
+        let outputs = PartialOutputs::default();
+        outputs.create(path).unwrap();
+        assert!(Path::new(&partial_path(path)).exists());
+        assert!(!Path::new(path).exists());
+
+        outputs.commit().unwrap();
+        assert!(Path::new(path).exists());
+        assert!(!Path::new(&partial_path(path)).exists());
+    }
+
+    #[test]
+    fn partial_outputs_drop_keeps_resumable_outputs() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("out.fq");
+        let path = path.to_str().unwrap();
+        let resumable = temp_dir.path().join("resumable.fq");
+        let resumable = resumable.to_str().unwrap();
+
+        let outputs = PartialOutputs::default();
+        outputs.create(path).unwrap();
+        File::create(outputs.create_resumable(resumable).unwrap()).unwrap();
+
+        drop(outputs);
+        assert!(!Path::new(&partial_path(path)).exists());
+        assert!(Path::new(&partial_path(resumable)).exists());
+    }
+
+    #[test]
+    fn partial_outputs_are_removed_with_the_last_clone() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("out.fq");
+        let path = path.to_str().unwrap();
+
+        let outputs = PartialOutputs::default();
+        let writer_outputs = outputs.clone();
+        writer_outputs.create(path).unwrap();
+
+        drop(outputs);
+        assert!(Path::new(&partial_path(path)).exists());
+        drop(writer_outputs);
+        assert!(!Path::new(&partial_path(path)).exists());
+    }
+
+    #[test]
+    fn partial_outputs_commit_returns_summaries() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("out.fq");
+        let path = path.to_str().unwrap();
+        let index = temp_dir.path().join("out.fq.fai");
+        let index = index.to_str().unwrap();
+
+        let outputs = PartialOutputs::default();
+        outputs.create(path).unwrap();
+        assert!(outputs.summary(path, "fq", "none").is_none());
+
+        outputs.summarize();
+        outputs.create(index).unwrap();
+        assert!(outputs.summary(path, "fq", "none").is_some());
+
+        let summaries = outputs.commit().unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].path(), path);
+    }
+}
@@ -0,0 +1,162 @@
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use indicatif::{HumanBytes, HumanDuration, ProgressBar, ProgressStyle};
+
+/// Progress of the spots processed across all worker threads
+pub struct Progress {
+    /// Number of spots to process
+    total: u64,
+    spots: AtomicU64,
+    bytes: AtomicU64,
+    start: Instant,
+    /// Live display (hidden unless stderr is a terminal)
+    bar: ProgressBar,
+}
+impl Progress {
+    pub fn new(total: u64) -> Result<Self> {
+        let bar = if std::io::stderr().is_terminal() {
+            ProgressBar::new(total)
+        } else {
+            ProgressBar::hidden()
+        };
+        bar.set_style(ProgressStyle::default_bar()
+            .template(
+                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {human_pos}/{human_len} spots ({per_sec}, {eta}) {msg}")?
+            .progress_chars("#>-"));
+        Ok(Self {
+            total,
+            spots: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            start: Instant::now(),
+            bar,
+        })
+    }
+
+    /// Records processed spots and the bytes written for them
+    pub fn inc(&self, spots: u64, bytes: u64) {
+        self.spots.fetch_add(spots, Ordering::Relaxed);
+        let total_bytes = self.bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        self.bar.inc(spots);
+        self.bar
+            .set_message(format!("{} written", HumanBytes(total_bytes)));
+    }
+
+    /// Records bytes written independently of the processed spots
+    ///
+    /// The live display only picks these up on the next call to [`Progress::inc`].
+    pub fn add_bytes(&self, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Removes the live display (before the final statistics are printed)
+    pub fn finish(&self) {
+        self.bar.finish_and_clear();
+    }
+
+    /// A single line summary of the progress for logs
+    pub fn summary(&self) -> String {
+        let spots = self.spots.load(Ordering::Relaxed);
+        let bytes = self.bytes.load(Ordering::Relaxed);
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 {
+            spots as f64 / elapsed
+        } else {
+            0.0
+        };
+        let percent = if self.total > 0 {
+            100.0 * spots as f64 / self.total as f64
+        } else {
+            100.0
+        };
+        let eta = if rate > 0.0 {
+            HumanDuration(Duration::from_secs_f64(
+                self.total.saturating_sub(spots) as f64 / rate,
+            ))
+            .to_string()
+        } else {
+            "unknown".to_string()
+        };
+        format!(
+            "Progress: {spots}/{} spots ({percent:.1}%) | {rate:.0} spots/s | ETA {eta} | {} written",
+            self.total,
+            HumanBytes(bytes)
+        )
+    }
+}
+
+/// Prints the progress summary to stderr at a fixed interval until it is dropped
+pub struct ProgressLogger {
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+impl ProgressLogger {
+    pub fn spawn(progress: Arc<Progress>, interval: Duration) -> Self {
+        let (stop, signal) = mpsc::channel::<()>();
+        let handle = std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = signal.recv_timeout(interval) {
+                progress.bar.suspend(|| eprintln!("{}", progress.summary()));
+            }
+            // A final line once all spots were processed
+            eprintln!("{}", progress.summary());
+        });
+        Self {
+            stop: Some(stop),
+            handle: Some(handle),
+        }
+    }
+}
+impl Drop for ProgressLogger {
+    fn drop(&mut self) {
+        // Disconnecting the channel stops the logging thread
+        self.stop.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Counts the bytes written to the inner handle as progress
+pub struct ProgressWriter<W: Write> {
+    inner: W,
+    progress: Arc<Progress>,
+}
+impl<W: Write> ProgressWriter<W> {
+    pub fn new(inner: W, progress: Arc<Progress>) -> Self {
+        Self { inner, progress }
+    }
+}
+impl<W: Write> Write for ProgressWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let num_written = self.inner.write(buf)?;
+        self.progress.add_bytes(num_written as u64);
+        Ok(num_written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Progress tests
+    #[test]
+    fn progress_summary_reports_counts() {
+        let progress = Arc::new(Progress::new(200).unwrap());
+        progress.inc(50, 1024);
+        let mut writer = ProgressWriter::new(Vec::new(), progress.clone());
+        writer.write_all(&[0; 1024]).unwrap();
+
+        let summary = progress.summary();
+        assert!(summary.starts_with("Progress: 50/200 spots (25.0%)"));
+        assert!(summary.ends_with("2.00 KiB written"));
+    }
+}
@@ -0,0 +1,178 @@
+use std::borrow::Cow;
+
+use anyhow::{bail, Result};
+
+use crate::cli::QualityOptions;
+
+/// ASCII offset of the quality scores stored in the archive
+pub(crate) const PHRED33_OFFSET: u8 = 33;
+
+/// ASCII offset of legacy Illumina quality scores
+const PHRED64_OFFSET: u8 = 64;
+
+/// Highest printable ASCII character
+const MAX_QUAL_CHAR: u8 = b'~';
+
+/// Maps a quality score to its Illumina 8-level bin
+fn illumina_bin(score: u8) -> u8 {
+    match score {
+        0..=1 => score,
+        2..=9 => 6,
+        10..=19 => 15,
+        20..=24 => 22,
+        25..=29 => 27,
+        30..=34 => 33,
+        35..=39 => 37,
+        _ => 40,
+    }
+}
+
+/// An output-time transformation of Phred+33 quality strings
+///
+/// All transforms are folded into a single lookup table over the quality characters.
+#[derive(Debug, Clone, Copy)]
+pub struct QualityTransform {
+    table: [u8; 256],
+    identity: bool,
+}
+impl QualityTransform {
+    pub fn new(opts: &QualityOptions) -> Result<Self> {
+        let offset = if opts.phred64 {
+            PHRED64_OFFSET
+        } else {
+            PHRED33_OFFSET
+        };
+        let max_score = MAX_QUAL_CHAR - offset;
+        for (name, value) in [
+            ("--qual-fixed", opts.qual_fixed),
+            ("--qual-cap", opts.qual_cap),
+        ] {
+            if let Some(score) = value.filter(|&score| score > max_score) {
+                bail!("Quality score {score} ({name}) is above the maximum representable score ({max_score})");
+            }
+        }
+
+        let identity = !opts.is_active();
+
+        let mut table = [0; 256];
+        for (byte, entry) in table.iter_mut().enumerate() {
+            let byte = byte as u8;
+
+            // Characters below the quality range are passed through untouched
+            if identity || byte < PHRED33_OFFSET {
+                *entry = byte;
+                continue;
+            }
+
+            let mut score = byte - PHRED33_OFFSET;
+            if opts.qual_bin {
+                score = illumina_bin(score);
+            }
+            if let Some(fixed) = opts.qual_fixed {
+                score = fixed;
+            }
+            if let Some(cap) = opts.qual_cap {
+                score = score.min(cap);
+            }
+            *entry = score.min(max_score) + offset;
+        }
+
+        Ok(Self { table, identity })
+    }
+
+    /// Transforms a quality string (borrowed if no transform is applied)
+    pub fn apply<'a>(&self, qual: &'a [u8]) -> Cow<'a, [u8]> {
+        if self.identity {
+            Cow::Borrowed(qual)
+        } else {
+            Cow::Owned(qual.iter().map(|&q| self.table[q as usize]).collect())
+        }
+    }
+}
+impl Default for QualityTransform {
+    fn default() -> Self {
+        Self::new(&QualityOptions::default()).expect("default quality options are valid")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transform(opts: QualityOptions) -> QualityTransform {
+        QualityTransform::new(&opts).unwrap()
+    }
+
+    // QualityTransform::apply tests
+    #[test]
+    fn default_is_identity() {
+        let qt = QualityTransform::default();
+        assert!(qt.identity);
+        assert!(matches!(qt.apply(b"I#5"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn illumina_binning() {
+        let qt = transform(QualityOptions {
+            qual_bin: true,
+            ..Default::default()
+        });
+        // Q0, Q5, Q12, Q23, Q28, Q31, Q38, Q41
+        assert_eq!(qt.apply(b"!&-8=@GJ").as_ref(), b"!'07<BFI");
+    }
+
+    #[test]
+    fn fixed_value() {
+        let qt = transform(QualityOptions {
+            qual_fixed: Some(30),
+            ..Default::default()
+        });
+        assert_eq!(qt.apply(b"#5I").as_ref(), b"???");
+    }
+
+    #[test]
+    fn cap_value() {
+        let qt = transform(QualityOptions {
+            qual_cap: Some(30),
+            ..Default::default()
+        });
+        assert_eq!(qt.apply(b"#5I").as_ref(), b"#5?");
+    }
+
+    #[test]
+    fn phred64_offset() {
+        let qt = transform(QualityOptions {
+            phred64: true,
+            ..Default::default()
+        });
+        assert_eq!(qt.apply(b"#5I").as_ref(), b"BTh");
+    }
+
+    #[test]
+    fn binning_then_cap_and_offset() {
+        let qt = transform(QualityOptions {
+            qual_bin: true,
+            qual_cap: Some(35),
+            phred64: true,
+            ..Default::default()
+        });
+        // Q41 -> 40 -> 35 -> 'c'
+        assert_eq!(qt.apply(b"J").as_ref(), b"c");
+    }
+
+    // QualityTransform::new tests
+    #[test]
+    fn rejects_unrepresentable_scores() {
+        assert!(QualityTransform::new(&QualityOptions {
+            qual_fixed: Some(63),
+            phred64: true,
+            ..Default::default()
+        })
+        .is_err());
+        assert!(QualityTransform::new(&QualityOptions {
+            qual_cap: Some(94),
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
@@ -1,5 +1,4 @@
-use std::fs::File;
-use std::io::BufWriter;
+use std::io::{BufWriter, Write};
 use std::path::Path;
 use std::sync::Arc;
 
@@ -14,12 +13,20 @@ use parking_lot::Mutex;
 
 use crate::cli::{BinseqFlavor, RecodeArgs};
 use crate::describe::describe_inner;
+use crate::manifest::{self, Manifest};
+use crate::partial::PartialOutputs;
 use crate::prefetch::identify_url;
-use crate::utils::get_num_records;
+use crate::progress::{Progress, ProgressLogger, ProgressWriter};
+use crate::quality::QualityTransform;
+use crate::spots::{SpotList, SpotQueue, SpotRange, SpotSampler, SPOT_CHUNK_SIZE};
+use crate::utils::{accession_name, get_num_records};
 
 const THREAD_UPDATE_INTERVAL: usize = 1024;
 
-pub fn recode(args: &RecodeArgs) -> Result<()> {
+/// Recodes the archive into a BINSEQ file
+///
+/// The output is created within `outputs` and removed unless all spots were written.
+pub fn recode(args: &RecodeArgs, outputs: PartialOutputs) -> Result<()> {
     args.validate()?;
     let accession = if !Path::new(&args.input.accession).exists() {
         eprintln!(
@@ -34,33 +41,136 @@ pub fn recode(args: &RecodeArgs) -> Result<()> {
         args.input.accession.to_string()
     };
 
-    match args.output.flavor {
+    // Resolve the spot range to process
+    let range = SpotRange::resolve(
+        get_num_records(&accession)?,
+        args.selection.start,
+        args.selection.stop,
+        args.selection.limit.map(|limit| limit as u64),
+    )?;
+    let spot_list = args
+        .selection
+        .spot_list
+        .as_deref()
+        .map(|path| SpotList::load(path, range))
+        .transpose()?;
+    let blocks = match &spot_list {
+        Some(list) => list.ranges().to_vec(),
+        None => vec![range],
+    };
+    let sampler = SpotSampler::from_options(
+        args.selection.sample_frac,
+        args.selection.sample_count,
+        args.selection.seed,
+        spot_list
+            .as_ref()
+            .map_or(std::slice::from_ref(&range), SpotList::ranges),
+    )?;
+    let selection = SpotSelection { spot_list, sampler };
+
+    let output_path = args.output.name();
+    if args.output.manifest.is_some() {
+        outputs.summarize();
+    }
+
+    // Report the decoded spots across all worker threads
+    let progress = Arc::new(Progress::new(blocks.iter().map(SpotRange::len).sum())?);
+    let logger = args
+        .runtime
+        .progress_interval()
+        .map(|interval| ProgressLogger::spawn(progress.clone(), interval));
+
+    let (num_reads, num_bases) = match args.output.flavor {
         BinseqFlavor::Binseq => recode_to_binseq(
             &accession,
-            &args.output.name(),
+            &output_path,
             args.primary_sid(),
             args.extended_sid(),
+            &blocks,
+            selection,
             args.runtime.threads(),
+            progress.clone(),
+            &outputs,
         ),
         BinseqFlavor::VBinseq => recode_to_vbinseq(
             &accession,
-            &args.output.name(),
+            &output_path,
             args.primary_sid(),
             args.extended_sid(),
+            &blocks,
+            selection,
             args.output.block_size,
             args.runtime.threads(),
+            QualityTransform::new(&args.quality)?,
+            progress.clone(),
+            &outputs,
         ),
+    }?;
+    progress.finish();
+    drop(logger);
+
+    // Rename the complete output to its final name
+    let summaries = outputs.commit()?;
+
+    // Record the provenance of the output
+    if let Some(path) = &args.output.manifest {
+        let entries = summaries
+            .iter()
+            .map(|summary| {
+                summary.set_counts(num_reads, num_bases);
+                summary.entry()
+            })
+            .collect();
+        let accession_id = accession_name(&args.input.accession);
+        Manifest::new(&accession_id, &accession, entries).save(path)?;
+        eprintln!("Wrote manifest of the output to {path}");
+    }
+
+    Ok(())
+}
+
+/// Creates the output file (hashed while writing if a manifest was requested)
+fn create_output(
+    path: &str,
+    format: &str,
+    progress: Arc<Progress>,
+    outputs: &PartialOutputs,
+) -> Result<BufWriter<Box<dyn Write + Send>>> {
+    let file = ProgressWriter::new(outputs.create(path)?, progress);
+    let writer: Box<dyn Write + Send> = match outputs.summary(path, format, "none") {
+        Some(summary) => Box::new(manifest::hash_file(file, &summary)),
+        None => Box::new(file),
+    };
+    Ok(BufWriter::new(writer))
+}
+
+/// Spots selected by the spot list and random sample
+struct SpotSelection {
+    spot_list: Option<SpotList>,
+    sampler: Option<SpotSampler>,
+}
+impl SpotSelection {
+    fn selects(&self, spot_id: u64) -> bool {
+        self.spot_list
+            .as_ref()
+            .is_none_or(|list| list.contains(spot_id))
+            && self.sampler.is_none_or(|sampler| sampler.keep(spot_id))
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn recode_to_binseq(
     accession: &str,
     output_path: &str,
     primary_sid: usize,
     extended_sid: Option<usize>,
+    blocks: &[SpotRange],
+    selection: SpotSelection,
     num_threads: u64,
-) -> Result<()> {
-    let stats = describe_inner(accession, 0, 100)?;
+    progress: Arc<Progress>,
+    outputs: &PartialOutputs,
+) -> Result<(u64, u64)> {
+    let stats = describe_inner(accession, blocks[0].start as usize, 100, 1)?;
     let sid_lengths = stats.segment_lengths();
 
     let slen = if sid_lengths[primary_sid].fract() == 0.0 {
@@ -79,7 +189,12 @@ fn recode_to_binseq(
         0
     };
 
-    let output = File::create(output_path).map(BufWriter::new)?;
+    let output = create_output(
+        output_path,
+        BinseqFlavor::Binseq.extension(),
+        progress.clone(),
+        outputs,
+    )?;
     let header = if xlen > 0 {
         BinseqHeader::new_extended(slen, xlen)
     } else {
@@ -92,46 +207,60 @@ fn recode_to_binseq(
         .build(output)?;
     let g_writer = Arc::new(Mutex::new(g_writer));
 
-    let num_records = get_num_records(accession)?;
-    let records_per_thread = num_records / num_threads;
-    let remainder = num_records % num_threads;
-
+    let selection = Arc::new(selection);
+    let queue = Arc::new(SpotQueue::new(blocks, SPOT_CHUNK_SIZE));
     let mut handles = Vec::new();
-    for tid in 0..num_threads {
-        let start = (tid * records_per_thread) + 1; // 1-indexed
-        let stop = if tid == num_threads - 1 {
-            start + records_per_thread + remainder - 1
-        } else {
-            start + records_per_thread - 1
-        };
+    for _ in 0..queue.num_workers(num_threads) {
         let t_accession = accession.to_string();
+        let queue = queue.clone();
         let mut t_writer = BinseqWriterBuilder::default()
             .header(header)
             .headless(true)
             .policy(policy)
             .build(Vec::new())?;
         let g_writer = g_writer.clone();
+        let selection = selection.clone();
+        let progress = progress.clone();
 
-        let handle = std::thread::spawn(move || -> Result<()> {
+        let handle = std::thread::spawn(move || -> Result<(u64, u64)> {
+            let mut num_decoded = 0usize;
+            let mut num_reported = 0usize;
+            let (mut num_reads, mut num_bases) = (0u64, 0u64);
+            // Pull chunks of spots until the queue is drained
             let reader = SraReader::new(&t_accession)?;
+            while let Some((_, SpotRange { start, stop })) = queue.next_chunk() {
+                for (iter_index, record) in reader.into_range_iter(start as i64, stop)?.enumerate()
+                {
+                    let record = record?;
+                    num_decoded += 1;
 
-            for (iter_index, record) in reader.into_range_iter(start as i64, stop)?.enumerate() {
-                let record = record?;
-                if xlen > 0 {
-                    let primary_seg = record.get_segment(primary_sid).unwrap();
-                    let extended_seg = record.get_segment(extended_sid.unwrap()).unwrap();
-                    t_writer.write_paired(0, primary_seg.seq(), extended_seg.seq())?;
-                } else {
-                    let primary_seg = record.get_segment(primary_sid).unwrap();
-                    t_writer.write_nucleotides(0, primary_seg.seq())?;
-                }
+                    // Skip spots which are not listed or outside of the random sample
+                    if !selection.selects(start + iter_index as u64) {
+                        continue;
+                    }
+
+                    if xlen > 0 {
+                        let primary_seg = record.get_segment(primary_sid).unwrap();
+                        let extended_seg = record.get_segment(extended_sid.unwrap()).unwrap();
+                        t_writer.write_paired(0, primary_seg.seq(), extended_seg.seq())?;
+                        num_reads += 2;
+                        num_bases += (primary_seg.seq().len() + extended_seg.seq().len()) as u64;
+                    } else {
+                        let primary_seg = record.get_segment(primary_sid).unwrap();
+                        t_writer.write_nucleotides(0, primary_seg.seq())?;
+                        num_reads += 1;
+                        num_bases += primary_seg.seq().len() as u64;
+                    }
 
-                // Process records at a constant interval
-                if iter_index.is_multiple_of(THREAD_UPDATE_INTERVAL) {
-                    {
-                        let mut global = g_writer.lock();
-                        global.ingest(&mut t_writer)?;
-                        global.flush()?;
+                    // Process records at a constant interval
+                    if num_decoded.is_multiple_of(THREAD_UPDATE_INTERVAL) {
+                        {
+                            let mut global = g_writer.lock();
+                            global.ingest(&mut t_writer)?;
+                            global.flush()?;
+                        }
+                        progress.inc((num_decoded - num_reported) as u64, 0);
+                        num_reported = num_decoded;
                     }
                 }
             }
@@ -142,29 +271,43 @@ fn recode_to_binseq(
                 global.ingest(&mut t_writer)?;
                 global.flush()?;
             }
+            progress.inc((num_decoded - num_reported) as u64, 0);
 
-            Ok(())
+            Ok((num_reads, num_bases))
         });
 
         handles.push(handle);
     }
 
+    let mut counts = (0, 0);
     for handle in handles {
-        handle.join().unwrap().unwrap();
+        let (num_reads, num_bases) = handle.join().expect("Thread panicked")?;
+        counts = (counts.0 + num_reads, counts.1 + num_bases);
     }
 
-    Ok(())
+    Ok(counts)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn recode_to_vbinseq(
     accession: &str,
     output_path: &str,
     primary_sid: usize,
     extended_sid: Option<usize>,
+    blocks: &[SpotRange],
+    selection: SpotSelection,
     block_size: usize,
     num_threads: u64,
-) -> Result<()> {
-    let output = File::create(output_path).map(BufWriter::new)?;
+    quality: QualityTransform,
+    progress: Arc<Progress>,
+    outputs: &PartialOutputs,
+) -> Result<(u64, u64)> {
+    let output = create_output(
+        output_path,
+        BinseqFlavor::VBinseq.extension(),
+        progress.clone(),
+        outputs,
+    )?;
     let header = if extended_sid.is_some() {
         VBinseqHeader::with_capacity(block_size as u64, true, true, true)
     } else {
@@ -177,51 +320,69 @@ fn recode_to_vbinseq(
         .build(output)?;
     let g_writer = Arc::new(Mutex::new(g_writer));
 
-    let num_records = get_num_records(accession)?;
-    let records_per_thread = num_records / num_threads;
-    let remainder = num_records % num_threads;
-
+    let selection = Arc::new(selection);
+    let queue = Arc::new(SpotQueue::new(blocks, SPOT_CHUNK_SIZE));
     let mut handles = Vec::new();
-    for tid in 0..num_threads {
-        let start = (tid * records_per_thread) + 1; // 1-indexed
-        let stop = if tid == num_threads - 1 {
-            start + records_per_thread + remainder - 1
-        } else {
-            start + records_per_thread - 1
-        };
+    for _ in 0..queue.num_workers(num_threads) {
         let t_accession = accession.to_string();
+        let queue = queue.clone();
         let mut t_writer = VBinseqWriterBuilder::default()
             .header(header)
             .headless(true)
             .policy(policy)
             .build(Vec::new())?;
         let g_writer = g_writer.clone();
+        let selection = selection.clone();
+        let progress = progress.clone();
 
-        let handle = std::thread::spawn(move || -> Result<()> {
+        let handle = std::thread::spawn(move || -> Result<(u64, u64)> {
+            let mut num_decoded = 0usize;
+            let mut num_reported = 0usize;
+            let (mut num_reads, mut num_bases) = (0u64, 0u64);
+            // Pull chunks of spots until the queue is drained
             let reader = SraReader::new(&t_accession)?;
+            while let Some((_, SpotRange { start, stop })) = queue.next_chunk() {
+                for (iter_index, record) in reader.into_range_iter(start as i64, stop)?.enumerate()
+                {
+                    let record = record?;
+                    num_decoded += 1;
 
-            for (iter_index, record) in reader.into_range_iter(start as i64, stop)?.enumerate() {
-                let record = record?;
-                if let Some(extended_sid) = extended_sid {
-                    let primary_seg = record.get_segment(primary_sid).unwrap();
-                    let extended_seg = record.get_segment(extended_sid).unwrap();
-                    t_writer.write_nucleotides_quality_paired(
-                        0,
-                        primary_seg.seq(),
-                        extended_seg.seq(),
-                        primary_seg.qual(),
-                        extended_seg.qual(),
-                    )?;
-                } else {
-                    let primary_seg = record.get_segment(primary_sid).unwrap();
-                    t_writer.write_nucleotides_quality(0, primary_seg.seq(), primary_seg.qual())?;
-                }
+                    // Skip spots which are not listed or outside of the random sample
+                    if !selection.selects(start + iter_index as u64) {
+                        continue;
+                    }
 
-                // Process records at a constant interval
-                if iter_index.is_multiple_of(THREAD_UPDATE_INTERVAL) {
-                    {
-                        let mut global = g_writer.lock();
-                        global.ingest(&mut t_writer)?;
+                    if let Some(extended_sid) = extended_sid {
+                        let primary_seg = record.get_segment(primary_sid).unwrap();
+                        let extended_seg = record.get_segment(extended_sid).unwrap();
+                        t_writer.write_nucleotides_quality_paired(
+                            0,
+                            primary_seg.seq(),
+                            extended_seg.seq(),
+                            &quality.apply(primary_seg.qual()),
+                            &quality.apply(extended_seg.qual()),
+                        )?;
+                        num_reads += 2;
+                        num_bases += (primary_seg.seq().len() + extended_seg.seq().len()) as u64;
+                    } else {
+                        let primary_seg = record.get_segment(primary_sid).unwrap();
+                        t_writer.write_nucleotides_quality(
+                            0,
+                            primary_seg.seq(),
+                            &quality.apply(primary_seg.qual()),
+                        )?;
+                        num_reads += 1;
+                        num_bases += primary_seg.seq().len() as u64;
+                    }
+
+                    // Process records at a constant interval
+                    if num_decoded.is_multiple_of(THREAD_UPDATE_INTERVAL) {
+                        {
+                            let mut global = g_writer.lock();
+                            global.ingest(&mut t_writer)?;
+                        }
+                        progress.inc((num_decoded - num_reported) as u64, 0);
+                        num_reported = num_decoded;
                     }
                 }
             }
@@ -231,18 +392,21 @@ fn recode_to_vbinseq(
                 let mut global = g_writer.lock();
                 global.ingest(&mut t_writer)?;
             }
+            progress.inc((num_decoded - num_reported) as u64, 0);
 
-            Ok(())
+            Ok((num_reads, num_bases))
         });
 
         handles.push(handle);
     }
 
+    let mut counts = (0, 0);
     for handle in handles {
-        handle.join().unwrap().unwrap();
+        let (num_reads, num_bases) = handle.join().expect("Thread panicked")?;
+        counts = (counts.0 + num_reads, counts.1 + num_bases);
     }
 
     g_writer.lock().finish()?;
 
-    Ok(())
+    Ok(counts)
 }
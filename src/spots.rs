@@ -0,0 +1,544 @@
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use anyhow::{anyhow, bail, Result};
+use serde::{Deserialize, Serialize};
+
+/// An inclusive range of (1-indexed) spot IDs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SpotRange {
+    pub start: u64,
+    pub stop: u64,
+}
+impl SpotRange {
+    pub fn new(start: u64, stop: u64) -> Self {
+        Self { start, stop }
+    }
+
+    /// Resolves the user-provided bounds against the number of spots in the archive
+    ///
+    /// `start` and `stop` default to the first and last spot, and `limit` caps the
+    /// number of spots processed from `start`.
+    pub fn resolve(
+        num_spots: u64,
+        start: Option<u64>,
+        stop: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Self> {
+        let start = start.unwrap_or(1);
+        if start == 0 {
+            bail!("Spot IDs are 1-indexed (start must be at least 1)");
+        }
+        if start > num_spots {
+            bail!(
+                "Provided start spot ({start}) is greater than the number of spots ({num_spots})"
+            );
+        }
+
+        let mut stop = stop.unwrap_or(num_spots);
+        if stop < start {
+            bail!("Provided stop spot ({stop}) is before the start spot ({start})");
+        }
+        if stop > num_spots {
+            eprintln!("Warning: Provided stop spot ({stop}) is greater than the number of spots ({num_spots}). Will process up to the last spot.");
+            stop = num_spots;
+        }
+
+        // Adjust the number of spots to process if a limit is provided
+        if let Some(limit) = limit {
+            let available = stop - start + 1;
+            if limit > available {
+                eprintln!("Warning: Provided spot limit ({limit}) is greater than the number of available spots ({available}). Will process all available spots.");
+            } else {
+                stop = start + limit - 1;
+            }
+        }
+
+        Ok(Self { start, stop })
+    }
+
+    /// Number of spots in the range
+    pub fn len(&self) -> u64 {
+        (self.stop + 1).saturating_sub(self.start)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Splits a set of ranges into chunks of at most `chunk_size` spots (in order)
+pub fn chunk_ranges(ranges: &[SpotRange], chunk_size: u64) -> Vec<SpotRange> {
+    ranges
+        .iter()
+        .flat_map(|range| {
+            (range.start..=range.stop)
+                .step_by(chunk_size as usize)
+                .map(move |start| SpotRange::new(start, (start + chunk_size - 1).min(range.stop)))
+        })
+        .collect()
+}
+
+/// Number of spots per chunk of a [`SpotQueue`]
+///
+/// Chunks are the unit of scheduling: smaller chunks balance the work across threads more
+/// evenly, but each chunk adds a spot range to `mark_spots` and a hand-off through the
+/// reorder stage of ordered output. Larger chunks in turn grow the reorder window, which
+/// holds up to two chunks per thread in memory.
+pub const SPOT_CHUNK_SIZE: u64 = 16 * 1024;
+
+/// A shared queue of small spot chunks which idle worker threads pull from
+///
+/// Chunks are handed out in spot order, so a slow (e.g. remote) chunk only stalls the
+/// thread decoding it while the other threads keep pulling the remaining chunks.
+#[derive(Debug)]
+pub struct SpotQueue {
+    chunks: Vec<SpotRange>,
+    next: AtomicUsize,
+}
+impl SpotQueue {
+    pub fn new(ranges: &[SpotRange], chunk_size: u64) -> Self {
+        Self {
+            chunks: chunk_ranges(ranges, chunk_size),
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Takes the next chunk along with its index (`None` once all chunks were taken)
+    pub fn next_chunk(&self) -> Option<(usize, SpotRange)> {
+        let idx = self.next.fetch_add(1, Ordering::Relaxed);
+        self.chunks.get(idx).map(|chunk| (idx, *chunk))
+    }
+
+    /// Number of worker threads to launch (no more than there are chunks)
+    pub fn num_workers(&self, num_threads: u64) -> u64 {
+        num_threads.min(self.chunks.len() as u64)
+    }
+}
+
+/// A selection of spot IDs stored as sorted, disjoint ranges
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpotList {
+    ranges: Vec<SpotRange>,
+}
+impl SpotList {
+    /// Reads a spot list file
+    pub fn from_path(path: &str) -> Result<Self> {
+        let file = File::open(path)?;
+        Self::from_reader(BufReader::new(file))
+    }
+
+    /// Reads a spot list file and restricts it to the spot range to process
+    pub fn load(path: &str, range: SpotRange) -> Result<Self> {
+        let list = Self::from_path(path)?.clip(range);
+        if list.is_empty() {
+            bail!(
+                "No spots of the spot list ({path}) are within the selected range ({}-{})",
+                range.start,
+                range.stop
+            );
+        }
+        Ok(list)
+    }
+
+    /// Parses one spot ID (`123`) or inclusive range (`100-200`) per line
+    ///
+    /// Empty lines and lines starting with `#` are ignored.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self> {
+        let mut ranges = Vec::new();
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parse = |id: &str| {
+                id.trim()
+                    .parse::<u64>()
+                    .map_err(|_| anyhow!("Invalid spot ID on line {}: {line}", idx + 1))
+            };
+            let range = match line.split_once('-') {
+                Some((start, stop)) => SpotRange::new(parse(start)?, parse(stop)?),
+                None => {
+                    let spot_id = parse(line)?;
+                    SpotRange::new(spot_id, spot_id)
+                }
+            };
+            if range.start == 0 || range.is_empty() {
+                bail!("Invalid spot range on line {}: {line}", idx + 1);
+            }
+            ranges.push(range);
+        }
+        Ok(Self::from_ranges(ranges))
+    }
+
+    /// Sorts and merges overlapping or adjacent ranges
+    pub fn from_ranges(mut ranges: Vec<SpotRange>) -> Self {
+        ranges.sort_unstable_by_key(|range| range.start);
+        let mut merged: Vec<SpotRange> = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            match merged.last_mut() {
+                Some(last) if range.start <= last.stop + 1 => last.stop = last.stop.max(range.stop),
+                _ => merged.push(range),
+            }
+        }
+        Self { ranges: merged }
+    }
+
+    pub fn ranges(&self) -> &[SpotRange] {
+        &self.ranges
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn contains(&self, spot_id: u64) -> bool {
+        let idx = self.ranges.partition_point(|range| range.stop < spot_id);
+        self.ranges
+            .get(idx)
+            .is_some_and(|range| range.start <= spot_id)
+    }
+
+    /// Removes the spots of another list
+    pub fn difference(&self, other: &SpotList) -> Self {
+        let mut ranges = Vec::new();
+        let mut idx = 0;
+        for range in &self.ranges {
+            // skip the ranges ending before this range
+            while other.ranges.get(idx).is_some_and(|o| o.stop < range.start) {
+                idx += 1;
+            }
+            let mut start = range.start;
+            for removed in other.ranges[idx..].iter() {
+                if removed.start > range.stop {
+                    break;
+                }
+                if removed.start > start {
+                    ranges.push(SpotRange::new(start, removed.start - 1));
+                }
+                start = removed.stop + 1;
+            }
+            if start <= range.stop {
+                ranges.push(SpotRange::new(start, range.stop));
+            }
+        }
+        Self { ranges }
+    }
+
+    /// Restricts the list to the spots within the bounds
+    pub fn clip(&self, bounds: SpotRange) -> Self {
+        let ranges = self
+            .ranges
+            .iter()
+            .map(|range| SpotRange::new(range.start.max(bounds.start), range.stop.min(bounds.stop)))
+            .filter(|range| !range.is_empty())
+            .collect();
+        Self { ranges }
+    }
+}
+
+/// SplitMix64 finalizer (a bijection over u64)
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// Seeded hash of a spot ID (distinct spot IDs never collide for a given seed)
+fn spot_hash(seed: u64, spot_id: u64) -> u64 {
+    splitmix64(spot_id ^ splitmix64(seed))
+}
+
+/// Reproducible random selection of spots
+///
+/// Spots are kept if their seeded hash falls below a threshold, so the selection only
+/// depends on the seed and the spot ID (and not on the number of threads).
+#[derive(Debug, Clone, Copy)]
+pub struct SpotSampler {
+    seed: u64,
+    threshold: u64,
+}
+impl SpotSampler {
+    /// Builds a sampler from the sampling options (`None` if no sampling is required)
+    pub fn from_options(
+        fraction: Option<f64>,
+        count: Option<u64>,
+        seed: u64,
+        ranges: &[SpotRange],
+    ) -> Result<Option<Self>> {
+        let available: u64 = ranges.iter().map(SpotRange::len).sum();
+        match (fraction, count) {
+            (Some(fraction), _) => Self::with_fraction(seed, fraction).map(Some),
+            (None, Some(0)) => bail!("Sample count must be greater than zero"),
+            (None, Some(count)) if count >= available => {
+                eprintln!("Warning: Provided sample count ({count}) is not smaller than the number of available spots ({available}). Will process all available spots.");
+                Ok(None)
+            }
+            (None, Some(count)) => Ok(Some(Self::with_count(seed, count, ranges))),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Keeps each spot with the given probability
+    pub fn with_fraction(seed: u64, fraction: f64) -> Result<Self> {
+        if !(fraction > 0.0 && fraction <= 1.0) {
+            bail!("Sample fraction must be in (0, 1]: {fraction}");
+        }
+        let threshold = if fraction == 1.0 {
+            u64::MAX
+        } else {
+            (fraction * u64::MAX as f64) as u64
+        };
+        Ok(Self { seed, threshold })
+    }
+
+    /// Keeps exactly `count` spots of the ranges (the spots with the smallest hashes)
+    ///
+    /// `count` must be non-zero.
+    pub fn with_count(seed: u64, count: u64, ranges: &[SpotRange]) -> Self {
+        // the heap never holds more than the available spots
+        let available: u64 = ranges.iter().map(SpotRange::len).sum();
+        let mut heap = BinaryHeap::with_capacity(count.min(available) as usize);
+        for spot_id in ranges.iter().flat_map(|range| range.start..=range.stop) {
+            let hash = spot_hash(seed, spot_id);
+            if (heap.len() as u64) < count {
+                heap.push(hash);
+            } else if heap.peek().is_some_and(|&max| hash < max) {
+                heap.pop();
+                heap.push(hash);
+            }
+        }
+        let threshold = heap.peek().copied().unwrap_or(0);
+        Self { seed, threshold }
+    }
+
+    /// Whether the spot is part of the sample
+    pub fn keep(&self, spot_id: u64) -> bool {
+        spot_hash(self.seed, spot_id) <= self.threshold
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // SpotRange::resolve tests
+    #[test]
+    fn resolve_defaults_to_full_archive() {
+        assert_eq!(
+            SpotRange::resolve(100, None, None, None).unwrap(),
+            SpotRange::new(1, 100)
+        );
+    }
+
+    #[test]
+    fn resolve_with_bounds_and_limit() {
+        assert_eq!(
+            SpotRange::resolve(100, Some(10), Some(50), None).unwrap(),
+            SpotRange::new(10, 50)
+        );
+        assert_eq!(
+            SpotRange::resolve(100, Some(10), Some(50), Some(5)).unwrap(),
+            SpotRange::new(10, 14)
+        );
+        assert_eq!(
+            SpotRange::resolve(100, Some(90), None, Some(50)).unwrap(),
+            SpotRange::new(90, 100)
+        );
+    }
+
+    #[test]
+    fn resolve_clamps_stop() {
+        assert_eq!(
+            SpotRange::resolve(100, None, Some(500), None).unwrap(),
+            SpotRange::new(1, 100)
+        );
+    }
+
+    #[test]
+    fn resolve_rejects_invalid_bounds() {
+        assert!(SpotRange::resolve(100, Some(0), None, None).is_err());
+        assert!(SpotRange::resolve(100, Some(101), None, None).is_err());
+        assert!(SpotRange::resolve(100, Some(50), Some(10), None).is_err());
+    }
+
+    // chunk_ranges tests
+    #[test]
+    fn chunk_ranges_caps_chunk_size() {
+        let chunks = chunk_ranges(&[SpotRange::new(1, 5), SpotRange::new(10, 10)], 2);
+        assert_eq!(
+            chunks,
+            vec![
+                SpotRange::new(1, 2),
+                SpotRange::new(3, 4),
+                SpotRange::new(5, 5),
+                SpotRange::new(10, 10)
+            ]
+        );
+    }
+
+    // SpotQueue tests
+    #[test]
+    fn spot_queue_hands_out_chunks_in_order() {
+        let queue = SpotQueue::new(&[SpotRange::new(1, 5), SpotRange::new(10, 10)], 2);
+        assert_eq!(queue.next_chunk(), Some((0, SpotRange::new(1, 2))));
+        assert_eq!(queue.next_chunk(), Some((1, SpotRange::new(3, 4))));
+        assert_eq!(queue.next_chunk(), Some((2, SpotRange::new(5, 5))));
+        assert_eq!(queue.next_chunk(), Some((3, SpotRange::new(10, 10))));
+        assert_eq!(queue.next_chunk(), None);
+        assert_eq!(queue.next_chunk(), None);
+    }
+
+    #[test]
+    fn spot_queue_is_drained_once_across_threads() {
+        let queue = std::sync::Arc::new(SpotQueue::new(&[SpotRange::new(1, 10_000)], 7));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let queue = queue.clone();
+                std::thread::spawn(move || {
+                    let mut taken = Vec::new();
+                    while let Some((idx, _)) = queue.next_chunk() {
+                        taken.push(idx);
+                    }
+                    taken
+                })
+            })
+            .collect();
+        let mut taken: Vec<usize> = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect();
+        taken.sort_unstable();
+        // 10,000 spots in chunks of 7
+        assert_eq!(taken, (0..1429).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn spot_queue_caps_workers_for_small_inputs() {
+        let queue = SpotQueue::new(&[SpotRange::new(1, 3)], SPOT_CHUNK_SIZE);
+        assert_eq!(queue.num_workers(8), 1);
+        assert_eq!(SpotQueue::new(&[], SPOT_CHUNK_SIZE).num_workers(8), 0);
+        assert_eq!(
+            SpotQueue::new(&[SpotRange::new(1, 100)], 10).num_workers(4),
+            4
+        );
+    }
+
+    #[test]
+    fn spot_queue_skips_unlisted_spots() {
+        // listed spots far apart are read as separate chunks
+        let list =
+            SpotList::from_ranges(vec![SpotRange::new(1, 2), SpotRange::new(10_000, 10_001)]);
+        let queue = SpotQueue::new(list.ranges(), SPOT_CHUNK_SIZE);
+        let mut decoded = Vec::new();
+        while let Some((_, chunk)) = queue.next_chunk() {
+            decoded.extend(chunk.start..=chunk.stop);
+        }
+        assert_eq!(decoded, vec![1, 2, 10_000, 10_001]);
+    }
+
+    // SpotList tests
+    #[test]
+    fn spot_list_parses_ids_and_ranges() {
+        let input = "# flagged spots\n5\n\n1-3\n4\n10 - 12\n";
+        let list = SpotList::from_reader(input.as_bytes()).unwrap();
+        assert_eq!(
+            list.ranges(),
+            &[SpotRange::new(1, 5), SpotRange::new(10, 12)]
+        );
+        assert!(list.contains(1));
+        assert!(list.contains(11));
+        assert!(!list.contains(7));
+        assert!(!list.contains(13));
+    }
+
+    #[test]
+    fn spot_list_rejects_invalid_lines() {
+        assert!(SpotList::from_reader("abc\n".as_bytes()).is_err());
+        assert!(SpotList::from_reader("0\n".as_bytes()).is_err());
+        assert!(SpotList::from_reader("5-3\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn spot_list_clip() {
+        let list = SpotList::from_ranges(vec![
+            SpotRange::new(1, 2),
+            SpotRange::new(5, 5),
+            SpotRange::new(100, 200),
+        ]);
+        assert_eq!(
+            list.clip(SpotRange::new(2, 150)).ranges(),
+            &[
+                SpotRange::new(2, 2),
+                SpotRange::new(5, 5),
+                SpotRange::new(100, 150)
+            ]
+        );
+    }
+
+    #[test]
+    fn spot_list_difference() {
+        let list = SpotList::from_ranges(vec![SpotRange::new(1, 10), SpotRange::new(20, 30)]);
+        let done = SpotList::from_ranges(vec![
+            SpotRange::new(1, 3),
+            SpotRange::new(5, 5),
+            SpotRange::new(9, 22),
+        ]);
+        assert_eq!(
+            list.difference(&done).ranges(),
+            &[
+                SpotRange::new(4, 4),
+                SpotRange::new(6, 8),
+                SpotRange::new(23, 30)
+            ]
+        );
+        assert!(list.difference(&list).is_empty());
+    }
+
+    // SpotSampler tests
+    #[test]
+    fn sampler_count_is_exact() {
+        let range = SpotRange::new(101, 1100);
+        let sampler = SpotSampler::with_count(7, 100, &[range]);
+        let kept = (range.start..=range.stop)
+            .filter(|&id| sampler.keep(id))
+            .count();
+        assert_eq!(kept, 100);
+    }
+
+    #[test]
+    fn sampler_count_beyond_available_keeps_all_spots() {
+        let range = SpotRange::new(1, 10);
+        let sampler = SpotSampler::with_count(7, u64::MAX, &[range]);
+        assert!((range.start..=range.stop).all(|id| sampler.keep(id)));
+    }
+
+    #[test]
+    fn sampler_fraction_is_approximate() {
+        let sampler = SpotSampler::with_fraction(7, 0.1).unwrap();
+        let kept = (1..=100_000).filter(|&id| sampler.keep(id)).count();
+        assert!((9_000..11_000).contains(&kept), "kept {kept} spots");
+    }
+
+    #[test]
+    fn sampler_depends_on_seed() {
+        let a = SpotSampler::with_fraction(1, 0.5).unwrap();
+        let b = SpotSampler::with_fraction(2, 0.5).unwrap();
+        assert!((1..=1000).any(|id| a.keep(id) != b.keep(id)));
+    }
+
+    #[test]
+    fn sampler_rejects_invalid_options() {
+        let range = SpotRange::new(1, 10);
+        assert!(SpotSampler::with_fraction(1, 0.0).is_err());
+        assert!(SpotSampler::with_fraction(1, 1.5).is_err());
+        assert!(SpotSampler::from_options(None, Some(0), 1, &[range]).is_err());
+        assert!(SpotSampler::from_options(None, Some(10), 1, &[range])
+            .unwrap()
+            .is_none());
+    }
+}
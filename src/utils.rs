@@ -1,3 +1,5 @@
+use std::path::Path;
+
 use anyhow::Result;
 use ncbi_vdb_sys::SraReader;
 
@@ -5,3 +7,33 @@ pub fn get_num_records(path: &str) -> Result<u64> {
     let reader = SraReader::new(path)?;
     Ok(reader.stop())
 }
+
+/// Derives the bare accession name from an accession or a path to an SRA file
+///
+/// I.e. `/data/SRR123456.lite.sra` becomes `SRR123456`
+pub fn accession_name(accession: &str) -> String {
+    let name = Path::new(accession)
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_else(|| accession.into());
+    match name.split_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem.to_string(),
+        _ => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // accession_name tests
+    #[test]
+    fn accession_name_from_bare_accession() {
+        assert_eq!(accession_name("SRR123456"), "SRR123456");
+    }
+
+    #[test]
+    fn accession_name_from_path() {
+        assert_eq!(accession_name("/data/SRR123456.lite.sra"), "SRR123456");
+    }
+}
@@ -11,7 +11,7 @@ fn test_describe_with_valid_sra_fixture() -> Result<()> {
     let fixtures = TestFixtures::ensure_fixtures()?;
 
     // Test describe with valid SRA file
-    let stats = describe_inner(&fixtures.small_variable_sra.to_string_lossy(), 0, 100)?;
+    let stats = describe_inner(&fixtures.small_variable_sra.to_string_lossy(), 0, 100, 1)?;
 
     // Verify we got meaningful results using available methods
     let segment_lengths = stats.segment_lengths();
@@ -35,12 +35,27 @@ fn test_describe_with_valid_sra_fixture() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_describe_threads_agree() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let path = fixtures.small_variable_sra.to_string_lossy();
+
+    // Chunks are merged in spot order regardless of the number of threads
+    let mut single = Vec::new();
+    describe_inner(&path, 0, 100_000, 1)?.pprint(&mut single)?;
+    let mut multi = Vec::new();
+    describe_inner(&path, 0, 100_000, 4)?.pprint(&mut multi)?;
+    assert_eq!(single, multi);
+
+    Ok(())
+}
+
 #[test]
 fn test_describe_with_invalid_sra_fixture() -> Result<()> {
     let fixtures = TestFixtures::ensure_fixtures()?;
 
     // Test describe with invalid SRA file - should fail
-    let result = describe_inner(&fixtures.invalid_sra.to_string_lossy(), 0, 10);
+    let result = describe_inner(&fixtures.invalid_sra.to_string_lossy(), 0, 10, 1);
 
     assert!(
         result.is_err(),
@@ -55,7 +70,7 @@ fn test_describe_with_corrupt_sra_fixture() -> Result<()> {
     let fixtures = TestFixtures::ensure_fixtures()?;
 
     // Test describe with corrupt SRA file - should fail
-    let result = describe_inner(&fixtures.corrupt_sra.to_string_lossy(), 0, 10);
+    let result = describe_inner(&fixtures.corrupt_sra.to_string_lossy(), 0, 10, 1);
 
     assert!(
         result.is_err(),
@@ -1,13 +1,17 @@
 use anyhow::Result;
+use arrow::ipc::reader::StreamReader;
 use assert_cmd::Command;
+use clap::Parser;
+use parquet::file::reader::{FileReader, SerializedFileReader};
 use predicates::prelude::*;
 use std::fs;
 use tempfile::TempDir;
 use xsra::cli::{
-    AccessionOptions, DumpOutput, FilterOptions, InputOptions, OutputFormat, Provider,
+    AccessionOptions, DumpOutput, FilterOptions, InputOptions, Provider, QualityOptions,
+    RuntimeOptions,
 };
 use xsra::dump::dump;
-use xsra::output::Compression;
+use xsra::partial::PartialOutputs;
 
 mod fixtures;
 use fixtures::setup::TestFixtures;
@@ -65,21 +69,23 @@ fn test_split_file_dump() -> Result<()> {
     let output = DumpOutput {
         outdir: temp_dir.path().to_string_lossy().to_string(),
         prefix: "test".to_string(),
-        compression: Compression::Uncompressed,
-        format: OutputFormat::Fastq,
-        named_pipes: false,
         split: true,
-        keep_empty: false,
+        ..Default::default()
     };
 
     let filter_opts = FilterOptions {
-        include: vec![],
-        skip_technical: false,
-        min_read_len: 1,
         limit: Some(100), // Limit to 100 spots for fast testing
+        ..Default::default()
     };
 
-    let result = dump(&input, 1, &output, filter_opts);
+    let result = dump(
+        &input,
+        &RuntimeOptions::parse_from(["xsra", "--threads", "1"]),
+        &output,
+        filter_opts,
+        &QualityOptions::default(),
+        PartialOutputs::default(),
+    );
     assert!(
         result.is_ok(),
         "Split dump command failed: {:?}",
@@ -140,11 +146,8 @@ fn test_empty_file_removal() -> Result<()> {
     let output = DumpOutput {
         outdir: temp_dir.path().to_string_lossy().to_string(),
         prefix: "test".to_string(),
-        compression: Compression::Uncompressed,
-        format: OutputFormat::Fastq,
-        named_pipes: false,
         split: true,
-        keep_empty: false,
+        ..Default::default()
     };
 
     let filter_opts = FilterOptions {
@@ -152,9 +155,17 @@ fn test_empty_file_removal() -> Result<()> {
         skip_technical: true,
         min_read_len: 10000, // Very high threshold to potentially create empty files
         limit: Some(50),     // Small limit for fast testing
+        ..Default::default()
     };
 
-    let result = dump(&input, 1, &output, filter_opts);
+    let result = dump(
+        &input,
+        &RuntimeOptions::parse_from(["xsra", "--threads", "1"]),
+        &output,
+        filter_opts,
+        &QualityOptions::default(),
+        PartialOutputs::default(),
+    );
     assert!(
         result.is_ok(),
         "Empty file removal test failed: {:?}",
@@ -229,3 +240,868 @@ fn test_fasta_output_cli() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_ordered_dump_is_thread_independent() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+
+    let run = |threads: &str| -> Result<Vec<u8>> {
+        let output = Command::cargo_bin("xsra")?
+            .arg("dump")
+            .arg(&fixtures.small_variable_sra)
+            .arg("--ordered")
+            .arg("-T")
+            .arg(threads)
+            .output()?;
+        assert!(output.status.success());
+        Ok(output.stdout)
+    };
+
+    let single = run("1")?;
+    let multi = run("4")?;
+    assert!(!single.is_empty());
+    assert_eq!(single, multi, "Ordered output should not depend on threads");
+
+    Ok(())
+}
+
+#[test]
+fn test_fastq_dump_defline_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(fixtures.small_variable_sra)
+        .arg("--defline")
+        .arg("fastq-dump")
+        .arg("--limit")
+        .arg("1");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("@small-variable.1 1 length="))
+        .stdout(predicate::str::contains("\n+small-variable.1 1 length="));
+
+    Ok(())
+}
+
+#[test]
+fn test_defline_template_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(fixtures.small_variable_sra)
+        .arg("--defline-seq")
+        .arg("$ac_$si/$ri")
+        .arg("--limit")
+        .arg("1")
+        .arg("-I")
+        .arg("0");
+
+    cmd.assert()
+        .success()
+        .stdout(predicate::str::starts_with("@small-variable_1/1\n"));
+
+    Ok(())
+}
+
+#[test]
+fn test_invalid_defline_template_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(fixtures.small_variable_sra)
+        .arg("--defline-seq")
+        .arg("$zz")
+        .arg("--limit")
+        .arg("1");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("Invalid placeholder"));
+
+    Ok(())
+}
+
+#[test]
+fn test_split3_dump_keeps_mates_in_sync() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let temp_dir = TempDir::new()?;
+
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(&fixtures.small_variable_sra)
+        .arg("--split-3")
+        .arg("-L")
+        .arg("50")
+        .arg("-o")
+        .arg(temp_dir.path())
+        .arg("-p")
+        .arg("test_")
+        .arg("--limit")
+        .arg("100")
+        .arg("-E");
+    cmd.assert().success();
+
+    let count_lines = |name: &str| -> Result<usize> {
+        Ok(fs::read_to_string(temp_dir.path().join(name))?
+            .lines()
+            .count())
+    };
+    assert_eq!(
+        count_lines("test_1.fq")?,
+        count_lines("test_2.fq")?,
+        "Paired files should contain the same number of records"
+    );
+    assert!(temp_dir.path().join("test_unpaired.fq").exists());
+
+    Ok(())
+}
+
+#[test]
+fn test_spot_filter_any_keeps_segments_in_sync() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let temp_dir = TempDir::new()?;
+
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(&fixtures.small_variable_sra)
+        .arg("-s")
+        .arg("-I")
+        .arg("0,1")
+        .arg("-L")
+        .arg("50")
+        .arg("--spot-filter")
+        .arg("any")
+        .arg("-o")
+        .arg(temp_dir.path())
+        .arg("-p")
+        .arg("test_")
+        .arg("--limit")
+        .arg("100")
+        .arg("-E");
+    cmd.assert().success();
+
+    let count_lines = |name: &str| -> Result<usize> {
+        Ok(fs::read_to_string(temp_dir.path().join(name))?
+            .lines()
+            .count())
+    };
+    // Only paired archives will have records for the second segment
+    let mate_lines = count_lines("test_1.fq")?;
+    if mate_lines > 0 {
+        assert_eq!(
+            count_lines("test_0.fq")?,
+            mate_lines,
+            "Segment files should contain the same number of records"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_concatenated_dump_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(fixtures.small_variable_sra)
+        .arg("--concatenate")
+        .arg("--concat-separator")
+        .arg("N")
+        .arg("-L")
+        .arg("0")
+        .arg("--limit")
+        .arg("10");
+
+    let output = cmd.output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    assert_eq!(
+        stdout.lines().count(),
+        40,
+        "Expected one record per spot (10 spots * 4 lines/record)"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_sam_output_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(fixtures.small_variable_sra)
+        .arg("-f")
+        .arg("s")
+        .arg("-L")
+        .arg("0")
+        .arg("--limit")
+        .arg("10");
+
+    let output = cmd.output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(stdout.starts_with("@HD\tVN:1.6\tSO:unsorted\n@RG\tID:small-variable"));
+
+    for record in stdout.lines().filter(|line| !line.starts_with('@')) {
+        let fields: Vec<&str> = record.split('\t').collect();
+        assert!(fields.len() >= 12, "Expected 11 mandatory fields and a tag");
+        let flag: u16 = fields[1].parse()?;
+        assert!([4, 77, 141].contains(&flag), "Unexpected FLAG: {flag}");
+        assert_eq!(fields[9].len(), fields[10].len());
+        assert_eq!(fields[11], "RG:Z:small-variable");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_tech_tag_requires_sam_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(fixtures.small_variable_sra)
+        .arg("--tech-tag")
+        .arg("bc")
+        .arg("--limit")
+        .arg("10");
+
+    cmd.assert().failure();
+
+    Ok(())
+}
+
+#[test]
+fn test_parquet_output_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let temp_dir = TempDir::new()?;
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(fixtures.small_variable_sra)
+        .arg("-f")
+        .arg("p")
+        .arg("--rows")
+        .arg("spot")
+        .arg("-L")
+        .arg("0")
+        .arg("--limit")
+        .arg("10")
+        .arg("-o")
+        .arg(temp_dir.path());
+    cmd.assert().success();
+
+    let path = temp_dir.path().join("small-variable.parquet");
+    let reader = SerializedFileReader::new(fs::File::open(path)?)?;
+    assert_eq!(
+        reader.metadata().file_metadata().num_rows(),
+        10,
+        "Expected one row per spot"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_arrow_ipc_output_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(fixtures.small_variable_sra)
+        .arg("-f")
+        .arg("i")
+        .arg("--limit")
+        .arg("10");
+
+    let output = cmd.output()?;
+    assert!(output.status.success());
+
+    let reader = StreamReader::try_new(std::io::Cursor::new(output.stdout), None)?;
+    let mut num_rows = 0;
+    for batch in reader {
+        let batch = batch?;
+        assert_eq!(batch.schema().field(0).name(), "spot_id");
+        num_rows += batch.num_rows();
+    }
+    assert!(num_rows >= 10, "Expected at least one row per spot");
+
+    Ok(())
+}
+
+#[test]
+fn test_quality_binning_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(fixtures.small_variable_sra)
+        .arg("--qual-bin")
+        .arg("--qual-cap")
+        .arg("30")
+        .arg("--limit")
+        .arg("10");
+
+    let output = cmd.output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let allowed = b"!\"'07<?";
+    for qual in stdout.lines().skip(3).step_by(4) {
+        assert!(
+            qual.bytes().all(|q| allowed.contains(&q)),
+            "Unexpected binned quality string: {qual}"
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_trimming_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(fixtures.small_variable_sra)
+        .arg("--trim-window")
+        .arg("4:30")
+        .arg("--trim-trailing-n")
+        .arg("--limit")
+        .arg("10");
+
+    let output = cmd.output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let lines: Vec<&str> = stdout.lines().collect();
+    for record in lines.chunks(4) {
+        assert_eq!(record[1].len(), record[3].len());
+        assert!(!record[1].ends_with('N'));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_content_filters_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(fixtures.small_variable_sra)
+        .arg("--max-n-frac")
+        .arg("0")
+        .arg("--min-mean-qual")
+        .arg("20")
+        .arg("--limit")
+        .arg("10");
+
+    let output = cmd.output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let lines: Vec<&str> = stdout.lines().collect();
+    for record in lines.chunks(4) {
+        assert!(!record[1].contains('N'));
+        let total: u64 = record[3].bytes().map(|q| (q - 33) as u64).sum();
+        assert!(total >= 20 * record[3].len() as u64);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_spot_range_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(fixtures.small_variable_sra)
+        .arg("-N")
+        .arg("5")
+        .arg("-X")
+        .arg("9")
+        .arg("-I")
+        .arg("0")
+        .arg("-L")
+        .arg("0")
+        .arg("--ordered");
+
+    let output = cmd.output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let headers: Vec<&str> = stdout.lines().step_by(4).collect();
+    assert_eq!(headers, vec!["@5.0", "@6.0", "@7.0", "@8.0", "@9.0"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_invalid_spot_range_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(fixtures.small_variable_sra)
+        .arg("--start")
+        .arg("9")
+        .arg("--stop")
+        .arg("5");
+
+    cmd.assert().failure();
+
+    Ok(())
+}
+
+#[test]
+fn test_sample_count_is_thread_independent() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+
+    let run = |threads: &str| -> Result<Vec<String>> {
+        let output = Command::cargo_bin("xsra")?
+            .arg("dump")
+            .arg(&fixtures.small_variable_sra)
+            .arg("-T")
+            .arg(threads)
+            .arg("-I")
+            .arg("0")
+            .arg("-L")
+            .arg("0")
+            .arg("--limit")
+            .arg("100")
+            .arg("--sample-count")
+            .arg("20")
+            .arg("--seed")
+            .arg("7")
+            .arg("--ordered")
+            .output()?;
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout)?;
+        Ok(stdout.lines().step_by(4).map(String::from).collect())
+    };
+
+    let single = run("1")?;
+    assert_eq!(single.len(), 20, "Expected exactly 20 sampled spots");
+    assert_eq!(single, run("4")?);
+
+    Ok(())
+}
+
+#[test]
+fn test_spot_list_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let temp_dir = TempDir::new()?;
+    let spot_list = temp_dir.path().join("spots.txt");
+    fs::write(&spot_list, "# flagged spots\n8\n3\n\n7-8\n")?;
+
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(fixtures.small_variable_sra)
+        .arg("-T")
+        .arg("2")
+        .arg("-I")
+        .arg("0")
+        .arg("-L")
+        .arg("0")
+        .arg("--spot-list")
+        .arg(&spot_list)
+        .arg("--ordered");
+
+    let output = cmd.output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    let headers: Vec<&str> = stdout.lines().step_by(4).collect();
+    assert_eq!(headers, vec!["@3.0", "@7.0", "@8.0"]);
+
+    Ok(())
+}
+
+#[test]
+fn test_invalid_spot_list_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let temp_dir = TempDir::new()?;
+    let spot_list = temp_dir.path().join("spots.txt");
+    fs::write(&spot_list, "3\nnot-a-spot\n")?;
+
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(fixtures.small_variable_sra)
+        .arg("--spot-list")
+        .arg(&spot_list);
+
+    cmd.assert().failure();
+
+    Ok(())
+}
+
+#[test]
+fn test_chunked_output_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let temp_dir = TempDir::new()?;
+    let outdir = temp_dir.path().join("chunks");
+
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(&fixtures.small_fixed_sra)
+        .arg("-T")
+        .arg("1")
+        .arg("-I")
+        .arg("0")
+        .arg("--limit")
+        .arg("5000")
+        .arg("--split")
+        .arg("-o")
+        .arg(&outdir)
+        .arg("--chunk-reads")
+        .arg("2048");
+    cmd.assert().success();
+
+    let mut parts: Vec<String> = fs::read_dir(&outdir)?
+        .map(|entry| entry.map(|e| e.file_name().to_string_lossy().to_string()))
+        .collect::<std::io::Result<_>>()?;
+    parts.sort();
+    assert!(parts.len() > 1, "Expected multiple parts: {parts:?}");
+
+    let mut total = 0;
+    for (idx, part) in parts.iter().enumerate() {
+        assert_eq!(part, &format!("seg_0.part{:04}.fq", idx + 1));
+        let num_reads = fs::read_to_string(outdir.join(part))?.lines().count() / 4;
+        assert!(num_reads > 0 && num_reads <= 2048);
+        total += num_reads;
+    }
+    assert_eq!(total, 5000);
+
+    Ok(())
+}
+
+#[test]
+fn test_chunked_output_small_parts_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let temp_dir = TempDir::new()?;
+
+    // parts smaller than a batch of spots hold exactly the requested reads (in either write mode)
+    for (name, extra) in [("unordered", None), ("ordered", Some("--ordered"))] {
+        let outdir = temp_dir.path().join(name);
+        let mut cmd = Command::cargo_bin("xsra")?;
+        cmd.arg("dump")
+            .arg(&fixtures.small_fixed_sra)
+            .arg("-T")
+            .arg("2")
+            .arg("-I")
+            .arg("0")
+            .arg("--limit")
+            .arg("5000")
+            .arg("--split")
+            .arg("-o")
+            .arg(&outdir)
+            .arg("--chunk-reads")
+            .arg("300")
+            .args(extra);
+        cmd.assert().success();
+
+        let mut parts = fs::read_dir(&outdir)?
+            .map(|entry| Ok(entry?.path()))
+            .collect::<Result<Vec<_>>>()?;
+        parts.sort();
+        let part_reads = parts
+            .iter()
+            .map(|path| Ok(fs::read_to_string(path)?.lines().count() / 4))
+            .collect::<Result<Vec<_>>>()?;
+        // 5000 reads in 16 full parts of 300 and a last part of 200
+        let mut expected = vec![300; 16];
+        expected.push(200);
+        assert_eq!(part_reads, expected);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_chunked_output_requires_split_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(fixtures.small_variable_sra)
+        .arg("--chunk-uncompressed-size")
+        .arg("1M");
+
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("requires --split or --split-3"));
+
+    Ok(())
+}
+
+#[test]
+fn test_resume_from_checkpoint_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let temp_dir = TempDir::new()?;
+    let resumed_dir = temp_dir.path().join("resumed");
+    let full_dir = temp_dir.path().join("full");
+
+    let run = |outdir: &std::path::Path, limit: &str, extra: &[&str]| -> Result<()> {
+        Command::cargo_bin("xsra")?
+            .arg("dump")
+            .arg(&fixtures.small_variable_sra)
+            .arg("-I")
+            .arg("0")
+            .arg("-L")
+            .arg("0")
+            .arg("--split")
+            .arg("--ordered")
+            .arg("--limit")
+            .arg(limit)
+            .arg("-o")
+            .arg(outdir)
+            .args(extra)
+            .assert()
+            .success();
+        Ok(())
+    };
+
+    // An interrupted dump which only completed the first 50 spots
+    run(&resumed_dir, "50", &["--checkpoint"])?;
+    assert!(resumed_dir.join("seg_checkpoint.json").exists());
+    run(&resumed_dir, "100", &["--resume"])?;
+    run(&full_dir, "100", &[])?;
+
+    assert_eq!(
+        fs::read_to_string(resumed_dir.join("seg_0.fq"))?,
+        fs::read_to_string(full_dir.join("seg_0.fq"))?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_split_outputs_are_renamed_on_success_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let temp_dir = TempDir::new()?;
+    let outdir = temp_dir.path().join("out");
+
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(fixtures.small_variable_sra)
+        .arg("--split")
+        .arg("-cg")
+        .arg("--limit")
+        .arg("100")
+        .arg("-o")
+        .arg(&outdir);
+    cmd.assert().success();
+
+    let names: Vec<String> = fs::read_dir(&outdir)?
+        .map(|entry| entry.map(|e| e.file_name().to_string_lossy().to_string()))
+        .collect::<std::io::Result<_>>()?;
+    assert!(!names.is_empty());
+    assert!(
+        names.iter().all(|name| name.ends_with(".fq.gz")),
+        "Unexpected outputs: {names:?}"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_manifest_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let temp_dir = TempDir::new()?;
+    let outdir = temp_dir.path().join("out");
+    let manifest_path = temp_dir.path().join("manifest.json");
+
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(&fixtures.small_variable_sra)
+        .arg("--split")
+        .arg("--limit")
+        .arg("100")
+        .arg("-o")
+        .arg(&outdir)
+        .arg("--manifest")
+        .arg(&manifest_path);
+    cmd.assert().success();
+
+    let manifest: serde_json::Value = serde_json::from_str(&fs::read_to_string(&manifest_path)?)?;
+    assert_eq!(manifest["xsra_version"], env!("CARGO_PKG_VERSION"));
+    let outputs = manifest["outputs"].as_array().unwrap();
+    assert_eq!(outputs.len(), fs::read_dir(&outdir)?.count());
+    for output in outputs {
+        let contents = fs::read_to_string(output["path"].as_str().unwrap())?;
+        assert_eq!(output["format"], "fq");
+        assert_eq!(output["compression"], "none");
+        assert_eq!(output["bytes"], contents.len() as u64);
+        assert_eq!(output["reads"], (contents.lines().count() / 4) as u64);
+        let bases: usize = contents.lines().skip(1).step_by(4).map(str::len).sum();
+        assert_eq!(output["bases"], bases as u64);
+        assert_eq!(output["sha256"].as_str().unwrap().len(), 64);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_compression_level_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let temp_dir = TempDir::new()?;
+    let outdir = temp_dir.path().join("out");
+
+    // zstd levels stop at 22
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(&fixtures.small_variable_sra)
+        .arg("-cz")
+        .arg("--compression-level")
+        .arg("23");
+    cmd.assert().failure().stderr(predicate::str::contains(
+        "Compression level 23 is out of range",
+    ));
+
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(&fixtures.small_variable_sra)
+        .arg("--split")
+        .arg("--limit")
+        .arg("100")
+        .arg("-cx")
+        .arg("--compression-level")
+        .arg("9")
+        .arg("-o")
+        .arg(&outdir);
+    cmd.assert().success();
+    for entry in fs::read_dir(&outdir)? {
+        let path = entry?.path();
+        assert!(path.to_str().unwrap().ends_with(".fq.xz"));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_bgzf_index_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let temp_dir = TempDir::new()?;
+    let outdir = temp_dir.path().join("out");
+
+    // indices require BGZF output
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(&fixtures.small_variable_sra)
+        .arg("-cg")
+        .arg("--index");
+    cmd.assert()
+        .failure()
+        .stderr(predicate::str::contains("require bgzip compression"));
+
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(&fixtures.small_variable_sra)
+        .arg("--split")
+        .arg("--limit")
+        .arg("100")
+        .arg("-fa")
+        .arg("-cb")
+        .arg("--index")
+        .arg("-o")
+        .arg(&outdir);
+    cmd.assert().success();
+
+    let outputs: Vec<_> = fs::read_dir(&outdir)?
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "bgz"))
+        .collect();
+    assert!(!outputs.is_empty());
+    for output in outputs {
+        let output = output.to_str().unwrap();
+        let gzi = fs::read(format!("{output}.gzi"))?;
+        let num_entries = u64::from_le_bytes(gzi[..8].try_into()?);
+        assert_eq!(gzi.len() as u64, 8 + 16 * num_entries);
+
+        let fai = fs::read_to_string(format!("{output}.fai"))?;
+        assert!(fai.lines().count() > 0);
+        for line in fai.lines() {
+            let fields: Vec<u64> = line
+                .split('\t')
+                .skip(1)
+                .map(|f| f.parse().unwrap())
+                .collect();
+            // single-line records
+            assert_eq!(fields[0], fields[2]);
+            assert_eq!(fields[0] + 1, fields[3]);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_tcp_sink_cli() -> Result<()> {
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?.to_string();
+    let reader = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut received = Vec::new();
+        stream.read_to_end(&mut received).unwrap();
+        received
+    });
+
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(&fixtures.small_variable_sra)
+        .arg("--limit")
+        .arg("10")
+        .arg("--tcp")
+        .arg(&addr);
+    let streamed = cmd.assert().success().get_output().stdout.clone();
+    assert!(streamed.is_empty());
+
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(&fixtures.small_variable_sra)
+        .arg("--limit")
+        .arg("10");
+    let expected = cmd.assert().success().get_output().stdout.clone();
+    assert_eq!(reader.join().unwrap(), expected);
+
+    Ok(())
+}
+
+#[test]
+fn test_stats_json_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let temp_dir = TempDir::new()?;
+    let stats_path = temp_dir.path().join("stats.json");
+
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(fixtures.small_variable_sra)
+        .arg("--limit")
+        .arg("10")
+        .arg("--stats-format")
+        .arg("json")
+        .arg("--stats-file")
+        .arg(&stats_path);
+    let output = cmd.output()?;
+    assert!(output.status.success());
+
+    let stats: serde_json::Value = serde_json::from_str(&fs::read_to_string(&stats_path)?)?;
+    assert_eq!(stats["num_spots"], 10);
+    let records = String::from_utf8(output.stdout)?.lines().count() / 4;
+    assert_eq!(stats["num_reads"], records as u64);
+    assert!(stats["elapsed_secs"].as_f64().is_some());
+
+    Ok(())
+}
+
+#[test]
+fn test_progress_interval_cli() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let mut cmd = Command::cargo_bin("xsra")?;
+    cmd.arg("dump")
+        .arg(fixtures.small_variable_sra)
+        .arg("--limit")
+        .arg("10")
+        .arg("--progress-interval")
+        .arg("1");
+
+    // A final progress line is written once all spots were processed
+    cmd.assert()
+        .success()
+        .stderr(predicate::str::contains("Progress: 10/10 spots (100.0%)"));
+
+    Ok(())
+}
@@ -118,3 +118,66 @@ fn test_recode_to_binseq_segment_variance_error() {
             .or(predicate::str::contains("file invalid")),
     );
 }
+
+#[test]
+fn test_recode_spot_range() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_xsra"));
+    let output_file = "test_recode_spot_range.vbq";
+    let assert = cmd
+        .arg("recode")
+        .arg(&fixtures.small_variable_sra)
+        .arg("-f")
+        .arg("v")
+        .arg("-I")
+        .arg("0")
+        .arg("--start")
+        .arg("10")
+        .arg("--stop")
+        .arg("20")
+        .arg("-n")
+        .arg(output_file)
+        .assert();
+
+    assert.success();
+
+    assert_ne!(
+        fs::metadata(output_file)?.len(),
+        0,
+        "output file should not be empty"
+    );
+    fs::remove_file(output_file)?;
+    Ok(())
+}
+
+#[test]
+fn test_recode_spot_list() -> Result<()> {
+    let fixtures = TestFixtures::ensure_fixtures()?;
+    let spot_list = "test_recode_spot_list.txt";
+    fs::write(spot_list, "1-5\n20\n")?;
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_xsra"));
+    let output_file = "test_recode_spot_list.vbq";
+    let assert = cmd
+        .arg("recode")
+        .arg(&fixtures.small_variable_sra)
+        .arg("-f")
+        .arg("v")
+        .arg("-I")
+        .arg("0")
+        .arg("--spot-list")
+        .arg(spot_list)
+        .arg("-n")
+        .arg(output_file)
+        .assert();
+
+    assert.success();
+
+    assert_ne!(
+        fs::metadata(output_file)?.len(),
+        0,
+        "output file should not be empty"
+    );
+    fs::remove_file(output_file)?;
+    fs::remove_file(spot_list)?;
+    Ok(())
+}